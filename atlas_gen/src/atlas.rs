@@ -0,0 +1,184 @@
+use std::collections::HashMap;
+
+use serde::{Serialize, Deserialize};
+
+use crate::packer::PackerConfig;
+
+/// A rectangle in atlas page pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PixelRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// A rectangle in normalized texture coordinates, `(0, 0)` being the top-left of a page.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct UvRect {
+    pub min: [f32; 2],
+    pub max: [f32; 2],
+}
+
+impl UvRect {
+    /// The rectangle as `[u_min, v_min, u_max, v_max]`.
+    pub fn to_array(self) -> [f32; 4] {
+        [self.min[0], self.min[1], self.max[0], self.max[1]]
+    }
+}
+
+/// Border widths, in sprite pixels, that stay unscaled when a sprite is drawn as a 9-slice.
+///
+/// The corners are drawn at their original size, the edges stretch along one axis and
+/// the centre stretches along both.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NineSlice {
+    pub left: u32,
+    pub top: u32,
+    pub right: u32,
+    pub bottom: u32,
+}
+
+impl NineSlice {
+    /// The same inset on every side.
+    pub fn uniform(inset: u32) -> Self {
+        Self {
+            left: inset,
+            top: inset,
+            right: inset,
+            bottom: inset,
+        }
+    }
+}
+
+/// One sprite in the atlas.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UiAtlasTexture {
+    pub name: String,
+    /// SHA-256 of the source image file, as lowercase hex.
+    pub hash: String,
+    /// Index into [`UiAtlas::page_names`].
+    pub page: u32,
+    /// Where the sprite sits on its page, excluding the extruded border.
+    pub rect: PixelRect,
+    pub uv: UvRect,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nine_slice: Option<NineSlice>,
+}
+
+impl UiAtlasTexture {
+    pub fn new(name: String, hash: String, page: u32, rect: PixelRect, atlas_width: u32, atlas_height: u32) -> Self {
+        // Extrusion keeps neighbouring sprites out of reach of the sampler, so the
+        // coordinates can cover the sprite edge to edge.
+        let (width, height) = (atlas_width as f32, atlas_height as f32);
+        let uv = UvRect {
+            min: [rect.x as f32 / width, rect.y as f32 / height],
+            max: [(rect.x + rect.width) as f32 / width, (rect.y + rect.height) as f32 / height],
+        };
+
+        Self {
+            name,
+            hash,
+            page,
+            rect,
+            uv,
+            nine_slice: None,
+        }
+    }
+}
+
+/// The on-disk layout of [`UiAtlas`], which has no name index.
+#[derive(Clone, Serialize, Deserialize)]
+struct UiAtlasFile {
+    entries: Vec<UiAtlasTexture>,
+    width: u32,
+    height: u32,
+    pages: Vec<String>,
+    packer: PackerConfig,
+}
+
+/// Description of a packed sprite sheet, as written by `atlas_gen` next to its pages.
+///
+/// Every page shares the same `width` and `height`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "UiAtlasFile", into = "UiAtlasFile")]
+pub struct UiAtlas {
+    entries: Vec<UiAtlasTexture>,
+    index: HashMap<String, usize>,
+    width: u32,
+    height: u32,
+    pages: Vec<String>,
+    packer: PackerConfig,
+}
+
+impl From<UiAtlasFile> for UiAtlas {
+    fn from(file: UiAtlasFile) -> Self {
+        let mut atlas = UiAtlas::new(file.width, file.height, file.pages, file.packer);
+        for entry in file.entries {
+            atlas.push_entry(entry);
+        }
+        atlas
+    }
+}
+
+impl From<UiAtlas> for UiAtlasFile {
+    fn from(atlas: UiAtlas) -> Self {
+        Self {
+            entries: atlas.entries,
+            width: atlas.width,
+            height: atlas.height,
+            pages: atlas.pages,
+            packer: atlas.packer,
+        }
+    }
+}
+
+impl UiAtlas {
+    pub fn new(width: u32, height: u32, pages: Vec<String>, packer: PackerConfig) -> Self {
+        Self {
+            entries: Vec::new(),
+            index: HashMap::new(),
+            width,
+            height,
+            pages,
+            packer,
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// File names of the atlas pages, relative to the description.
+    pub fn page_names(&self) -> &[String] {
+        &self.pages
+    }
+
+    /// Settings the atlas was packed with, so a rebuild can tell whether slots are reusable.
+    pub fn packer(&self) -> &PackerConfig {
+        &self.packer
+    }
+
+    pub fn entries(&self) -> &[UiAtlasTexture] {
+        &self.entries
+    }
+
+    pub fn get_entry_by_name(&self, name: &str) -> Option<&UiAtlasTexture> {
+        self.index.get(name).map(|&index| &self.entries[index])
+    }
+
+    /// Adds an entry, replacing any existing entry with the same name.
+    pub(crate) fn push_entry(&mut self, entry: UiAtlasTexture) {
+        match self.index.get(&entry.name) {
+            Some(&index) => self.entries[index] = entry,
+            None => {
+                self.index.insert(entry.name.clone(), self.entries.len());
+                self.entries.push(entry);
+            }
+        }
+    }
+}
@@ -0,0 +1,44 @@
+use std::path::PathBuf;
+
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum AtlasError {
+    #[error("failed to access {path}")]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("failed to walk the input directory")]
+    Walk(#[from] walkdir::Error),
+    #[error("invalid glob pattern")]
+    Glob(#[from] globset::Error),
+    #[error("failed to read or write image {path}")]
+    Image {
+        path: PathBuf,
+        #[source]
+        source: image::ImageError,
+    },
+    #[error("failed to serialize the atlas description")]
+    Json(#[from] serde_json::Error),
+    #[error("failed to read 9-slice insets from {path}")]
+    Sidecar {
+        path: PathBuf,
+        #[source]
+        source: serde_json::Error,
+    },
+    #[error("invalid 9-slice suffix in {0}, expected `.9-<inset>` or `.9-<left>-<top>-<right>-<bottom>`")]
+    NineSliceSuffix(PathBuf),
+    #[error("9-slice insets of sprite {name} are wider or taller than the sprite")]
+    NineSliceTooLarge {
+        name: String,
+    },
+    #[error("no sprites matched in {0}")]
+    NoSprites(PathBuf),
+    #[error("sprite {name} does not fit on a {max_size}x{max_size} atlas page")]
+    SpriteTooLarge {
+        name: String,
+        max_size: u32,
+    },
+}
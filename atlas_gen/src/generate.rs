@@ -0,0 +1,326 @@
+use std::{fs, io, path::{Path, PathBuf}};
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use image::{DynamicImage, GenericImageView, RgbaImage};
+use sha2::{Digest, Sha256};
+use walkdir::WalkDir;
+
+use crate::{atlas::{NineSlice, PixelRect, UiAtlas, UiAtlasTexture}, error::AtlasError, packer::{PackerConfig, Placement, pack, pack_preserving}};
+
+/// What to pack and how to name the resulting pages.
+#[derive(Debug, Clone)]
+pub struct AtlasOptions {
+    /// Directory searched recursively for sprites.
+    pub input_dir: PathBuf,
+    /// Globs, relative to `input_dir`, a sprite must match to be packed.
+    pub include: Vec<String>,
+    /// Globs, relative to `input_dir`, that exclude an otherwise included sprite.
+    pub exclude: Vec<String>,
+    /// File name of the atlas image, e.g. `atlas.png`. When the sprites spill onto
+    /// several pages they are numbered: `atlas_0.png`, `atlas_1.png`, ...
+    pub image_name: String,
+    pub packer: PackerConfig,
+}
+
+impl Default for AtlasOptions {
+    fn default() -> Self {
+        Self {
+            input_dir: PathBuf::from("assets"),
+            include: vec!["**/*.png".to_string()],
+            exclude: Vec::new(),
+            image_name: "atlas.png".to_string(),
+            packer: PackerConfig::default(),
+        }
+    }
+}
+
+/// A packed atlas description together with the pixels of each page.
+pub struct GeneratedAtlas {
+    pub atlas: UiAtlas,
+    pub pages: Vec<RgbaImage>,
+    /// File names of the pages of the atlas this one replaces that it no longer has,
+    /// e.g. `atlas_2.png` after shrinking from three pages to two.
+    pub stale_pages: Vec<String>,
+}
+
+impl GeneratedAtlas {
+    /// Writes the description to `json_path` and every page into the same directory,
+    /// and deletes the stale pages from it.
+    ///
+    /// The pages always go next to the JSON file: the description refers to them by
+    /// file name only, and lib-render loads them from the directory of the description.
+    pub fn save(&self, json_path: &Path) -> Result<(), AtlasError> {
+        let image_dir = json_path.parent().unwrap_or(Path::new(""));
+        for (file_name, page) in self.atlas.page_names().iter().zip(&self.pages) {
+            let path = image_dir.join(file_name);
+            page.save(&path).map_err(|source| AtlasError::Image { path, source })?;
+        }
+
+        for file_name in &self.stale_pages {
+            let path = image_dir.join(file_name);
+            if let Err(source) = fs::remove_file(&path)
+                && source.kind() != io::ErrorKind::NotFound
+            {
+                return Err(AtlasError::Io { path, source });
+            }
+        }
+
+        let json_string = serde_json::to_string_pretty(&self.atlas)?;
+        fs::write(json_path, json_string).map_err(|source| AtlasError::Io { path: json_path.to_path_buf(), source })
+    }
+}
+
+fn build_glob_set(patterns: &[String]) -> Result<GlobSet, AtlasError> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern)?);
+    }
+    Ok(builder.build()?)
+}
+
+/// Reads an atlas description previously written by [`GeneratedAtlas::save`].
+pub fn read_atlas(json_path: &Path) -> Result<UiAtlas, AtlasError> {
+    let json_string = fs::read_to_string(json_path).map_err(|source| AtlasError::Io { path: json_path.to_path_buf(), source })?;
+    Ok(serde_json::from_str(&json_string)?)
+}
+
+/// Splits a `.9-<inset>` or `.9-<left>-<top>-<right>-<bottom>` suffix off a sprite name.
+///
+/// Returns `Ok(None)` for names without the suffix.
+fn parse_nine_slice_suffix(name: &str, path: &Path) -> Result<Option<(String, NineSlice)>, AtlasError> {
+    let Some((base, suffix)) = name.rsplit_once(".9-") else {
+        return Ok(None);
+    };
+
+    let insets = suffix
+        .split('-')
+        .map(str::parse)
+        .collect::<Result<Vec<u32>, _>>()
+        .map_err(|_| AtlasError::NineSliceSuffix(path.to_path_buf()))?;
+    let nine_slice = match insets[..] {
+        [inset] => NineSlice::uniform(inset),
+        [left, top, right, bottom] => NineSlice { left, top, right, bottom },
+        _ => return Err(AtlasError::NineSliceSuffix(path.to_path_buf())),
+    };
+    Ok(Some((base.to_string(), nine_slice)))
+}
+
+/// Reads the `<stem>.9.json` file next to a sprite, if there is one.
+fn read_nine_slice_sidecar(path: &Path) -> Result<Option<NineSlice>, AtlasError> {
+    let sidecar = path.with_extension("9.json");
+    if !sidecar.is_file() {
+        return Ok(None);
+    }
+
+    let json_string = fs::read_to_string(&sidecar).map_err(|source| AtlasError::Io { path: sidecar.clone(), source })?;
+    serde_json::from_str(&json_string)
+        .map(Some)
+        .map_err(|source| AtlasError::Sidecar { path: sidecar, source })
+}
+
+/// A sprite file picked up from the input directory.
+struct SpriteFile {
+    path: PathBuf,
+    name: String,
+    nine_slice: Option<NineSlice>,
+}
+
+/// Finds every sprite under `options.input_dir` that passes the include and exclude
+/// filters, named by its path relative to the input directory without the extension.
+///
+/// 9-slice insets come from a `<stem>.9.json` sidecar next to the sprite, or else from
+/// a `.9-<inset>` / `.9-<left>-<top>-<right>-<bottom>` suffix on the file name, which is
+/// dropped from the sprite name: `button.9-6.png` becomes `button`.
+///
+/// Sprites are returned sorted by name so the atlas does not depend on directory order.
+fn collect_sprites(options: &AtlasOptions) -> Result<Vec<SpriteFile>, AtlasError> {
+    let include = build_glob_set(&options.include)?;
+    let exclude = build_glob_set(&options.exclude)?;
+
+    let mut sprites = Vec::new();
+    for entry in WalkDir::new(&options.input_dir).sort_by_file_name() {
+        let entry = entry?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+
+        let relative = entry.path().strip_prefix(&options.input_dir).unwrap_or(entry.path());
+        if !include.is_match(relative) || exclude.is_match(relative) {
+            continue;
+        }
+
+        let name = relative
+            .with_extension("")
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        let (name, suffix_nine_slice) = match parse_nine_slice_suffix(&name, entry.path())? {
+            Some((name, nine_slice)) => (name, Some(nine_slice)),
+            None => (name, None),
+        };
+        let nine_slice = read_nine_slice_sidecar(entry.path())?.or(suffix_nine_slice);
+
+        sprites.push(SpriteFile {
+            path: entry.path().to_path_buf(),
+            name,
+            nine_slice,
+        });
+    }
+
+    if sprites.is_empty() {
+        return Err(AtlasError::NoSprites(options.input_dir.clone()));
+    }
+    sprites.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(sprites)
+}
+
+/// A source sprite read from disk.
+struct Sprite {
+    name: String,
+    hash: String,
+    image: DynamicImage,
+    nine_slice: Option<NineSlice>,
+}
+
+fn load_sprites(options: &AtlasOptions) -> Result<Vec<Sprite>, AtlasError> {
+    let mut sprites = Vec::new();
+    for SpriteFile { path, name, nine_slice } in collect_sprites(options)? {
+        let bytes = fs::read(&path).map_err(|source| AtlasError::Io { path: path.clone(), source })?;
+        let hash = Sha256::digest(&bytes).iter().map(|byte| format!("{byte:02x}")).collect();
+        let image = image::load_from_memory(&bytes).map_err(|source| AtlasError::Image { path, source })?;
+
+        if let Some(insets) = nine_slice {
+            let (width, height) = image.dimensions();
+            let too_wide = insets.left.checked_add(insets.right).is_none_or(|sum| sum > width);
+            let too_tall = insets.top.checked_add(insets.bottom).is_none_or(|sum| sum > height);
+            if too_wide || too_tall {
+                return Err(AtlasError::NineSliceTooLarge { name });
+            }
+        }
+        sprites.push(Sprite { name, hash, image, nine_slice });
+    }
+    Ok(sprites)
+}
+
+fn page_names(image_name: &str, page_count: u32) -> Vec<String> {
+    if page_count == 1 {
+        return vec![image_name.to_string()];
+    }
+
+    let path = Path::new(image_name);
+    let stem = path.file_stem().map(|stem| stem.to_string_lossy()).unwrap_or_default();
+    let extension = path.extension().map(|extension| extension.to_string_lossy()).unwrap_or("png".into());
+    (0..page_count).map(|page| format!("{stem}_{page}.{extension}")).collect()
+}
+
+/// Packs every sprite from scratch.
+pub fn generate_texture_atlas(options: &AtlasOptions) -> Result<GeneratedAtlas, AtlasError> {
+    build_atlas(options, load_sprites(options)?, None)
+}
+
+/// Rebuilds `previous` from the current sprites, or returns `None` when neither the
+/// sprites nor the packing settings changed.
+///
+/// Sprites that keep their size stay in the slot they had in `previous` whenever
+/// possible, so the regenerated pages differ as little as possible. Pages of `previous`
+/// the new atlas doesn't have are listed in its `stale_pages`.
+pub fn update_texture_atlas(options: &AtlasOptions, previous: &UiAtlas) -> Result<Option<GeneratedAtlas>, AtlasError> {
+    let sprites = load_sprites(options)?;
+
+    let same_settings = *previous.packer() == options.packer;
+    let same_pages = previous.page_names() == page_names(&options.image_name, previous.page_names().len() as u32);
+    if same_settings && same_pages && has_sprites(previous, &sprites) {
+        return Ok(None);
+    }
+
+    // Slots are only comparable when they were sized with the same padding and extrusion.
+    let mut generated = build_atlas(options, sprites, same_settings.then_some(previous))?;
+    let pages = generated.atlas.page_names();
+    generated.stale_pages = previous.page_names().iter().filter(|&page| !pages.contains(page)).cloned().collect();
+    Ok(Some(generated))
+}
+
+/// Where a sprite was placed in `atlas`, if it is there with the same size.
+fn placement_of(atlas: &UiAtlas, name: &str, width: u32, height: u32) -> Option<Placement> {
+    atlas.get_entry_by_name(name)
+        .filter(|entry| entry.rect.width == width && entry.rect.height == height)
+        .map(|entry| Placement {
+            page: entry.page,
+            x: entry.rect.x,
+            y: entry.rect.y,
+        })
+}
+
+/// Returns true if `atlas` holds exactly these sprites with the same content hashes
+/// and 9-slice insets.
+fn has_sprites(atlas: &UiAtlas, sprites: &[Sprite]) -> bool {
+    atlas.entries().len() == sprites.len()
+        && sprites.iter().all(|sprite| {
+            atlas.get_entry_by_name(&sprite.name)
+                .is_some_and(|entry| entry.hash == sprite.hash && entry.nine_slice == sprite.nine_slice)
+        })
+}
+
+fn build_atlas(options: &AtlasOptions, sprites: Vec<Sprite>, previous: Option<&UiAtlas>) -> Result<GeneratedAtlas, AtlasError> {
+    let config = &options.packer;
+    let sizes: Vec<(u32, u32)> = sprites.iter().map(|sprite| sprite.image.dimensions()).collect();
+    let packed = match previous {
+        Some(previous) => {
+            let placements: Vec<_> = sprites
+                .iter()
+                .zip(&sizes)
+                .map(|(sprite, &(width, height))| placement_of(previous, &sprite.name, width, height))
+                .collect();
+            pack_preserving(&sizes, &placements, config)
+        }
+        None => pack(&sizes, config),
+    };
+    let packed = packed.map_err(|index| AtlasError::SpriteTooLarge {
+        name: sprites[index].name.clone(),
+        max_size: config.max_size,
+    })?;
+
+    let mut pages = vec![RgbaImage::new(packed.width, packed.height); packed.page_count as usize];
+    let mut atlas_data = UiAtlas::new(packed.width, packed.height, page_names(&options.image_name, packed.page_count), *config);
+
+    for (sprite, placement) in sprites.into_iter().zip(packed.placements) {
+        let (image_width, image_height) = sprite.image.dimensions();
+        copy_extruded(&mut pages[placement.page as usize], &sprite.image.to_rgba8(), placement.x, placement.y, config.extrude);
+        let rect = PixelRect {
+            x: placement.x,
+            y: placement.y,
+            width: image_width,
+            height: image_height,
+        };
+        let mut entry = UiAtlasTexture::new(sprite.name, sprite.hash, placement.page, rect, packed.width, packed.height);
+        entry.nine_slice = sprite.nine_slice;
+        atlas_data.push_entry(entry);
+    }
+
+    Ok(GeneratedAtlas {
+        atlas: atlas_data,
+        pages,
+        stale_pages: Vec::new(),
+    })
+}
+
+/// Copies `image` onto `page` with its top-left corner at (`x`, `y`), repeating its
+/// outermost pixels `extrude` times in every direction.
+fn copy_extruded(page: &mut RgbaImage, image: &RgbaImage, x: u32, y: u32, extrude: u32) {
+    let (width, height) = image.dimensions();
+    let extrude = extrude as i64;
+
+    for dy in -extrude..height as i64 + extrude {
+        for dx in -extrude..width as i64 + extrude {
+            let source_x = dx.clamp(0, width as i64 - 1) as u32;
+            let source_y = dy.clamp(0, height as i64 - 1) as u32;
+            let target_x = x as i64 + dx;
+            let target_y = y as i64 + dy;
+
+            if target_x >= 0 && target_y >= 0 && (target_x as u32) < page.width() && (target_y as u32) < page.height() {
+                page.put_pixel(target_x as u32, target_y as u32, *image.get_pixel(source_x, source_y));
+            }
+        }
+    }
+}
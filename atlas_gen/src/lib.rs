@@ -0,0 +1,15 @@
+//! Packs UI sprites into atlas pages and describes where each sprite ended up.
+//!
+//! The atlas description in [`atlas`] is always available so that renderers can load
+//! atlases; generating them requires the `generate` feature.
+
+pub mod atlas;
+pub mod packer;
+#[cfg(feature = "generate")]
+pub mod error;
+#[cfg(feature = "generate")]
+mod generate;
+
+pub use crate::{atlas::UiAtlas, packer::PackerConfig};
+#[cfg(feature = "generate")]
+pub use crate::{error::AtlasError, generate::{AtlasOptions, GeneratedAtlas, generate_texture_atlas, read_atlas, update_texture_atlas}};
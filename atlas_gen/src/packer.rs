@@ -0,0 +1,432 @@
+use serde::{Deserialize, Serialize};
+
+/// Options controlling how sprites are laid out on atlas pages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PackerConfig {
+    /// Largest width and height a single page may have.
+    pub max_size: u32,
+    /// Round page dimensions up to the next power of two.
+    pub power_of_two: bool,
+    /// Empty pixels left between neighbouring sprites.
+    pub padding: u32,
+    /// Pixels of each sprite's border repeated outward to stop filtering from bleeding.
+    pub extrude: u32,
+}
+
+impl Default for PackerConfig {
+    fn default() -> Self {
+        Self {
+            // wgpu's `max_texture_dimension_2d` under `downlevel_webgl2_defaults`
+            max_size: 2048,
+            power_of_two: true,
+            padding: 1,
+            extrude: 1,
+        }
+    }
+}
+
+/// Where a sprite ended up: the page it was placed on and the top-left corner
+/// of the sprite itself (inside its extrusion border).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Placement {
+    pub page: u32,
+    pub x: u32,
+    pub y: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct SkylineNode {
+    x: u32,
+    y: u32,
+    width: u32,
+}
+
+/// Bottom-left skyline packer for a single page.
+struct Skyline {
+    width: u32,
+    height: u32,
+    nodes: Vec<SkylineNode>,
+    used_width: u32,
+    used_height: u32,
+}
+
+impl Skyline {
+    fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            nodes: vec![SkylineNode { x: 0, y: 0, width }],
+            used_width: 0,
+            used_height: 0,
+        }
+    }
+
+    /// Returns the y coordinate a rectangle starting at node `index` would rest at,
+    /// or `None` if it would not fit there.
+    fn fit(&self, index: usize, width: u32, height: u32) -> Option<u32> {
+        let x = self.nodes[index].x;
+        if x + width > self.width {
+            return None;
+        }
+
+        let mut remaining = width as i64;
+        let mut y = 0;
+        for node in &self.nodes[index..] {
+            if remaining <= 0 {
+                break;
+            }
+            y = y.max(node.y);
+            if y + height > self.height {
+                return None;
+            }
+            remaining -= node.width as i64;
+        }
+        Some(y)
+    }
+
+    fn insert(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        let mut best: Option<(usize, u32)> = None;
+        for index in 0..self.nodes.len() {
+            if let Some(y) = self.fit(index, width, height) {
+                let is_better = match best {
+                    None => true,
+                    Some((best_index, best_y)) => {
+                        y < best_y || (y == best_y && self.nodes[index].x < self.nodes[best_index].x)
+                    }
+                };
+                if is_better {
+                    best = Some((index, y));
+                }
+            }
+        }
+
+        let (index, y) = best?;
+        let x = self.nodes[index].x;
+        self.add_level(index, x, y + height, width);
+        self.used_width = self.used_width.max(x + width);
+        self.used_height = self.used_height.max(y + height);
+        Some((x, y))
+    }
+
+    fn add_level(&mut self, index: usize, x: u32, y: u32, width: u32) {
+        self.nodes.insert(index, SkylineNode { x, y, width });
+
+        // Shrink or drop the nodes the new level now covers.
+        let right = x + width;
+        let next = index + 1;
+        while next < self.nodes.len() {
+            let node = self.nodes[next];
+            if node.x >= right {
+                break;
+            }
+            let node_right = node.x + node.width;
+            if node_right <= right {
+                self.nodes.remove(next);
+            } else {
+                self.nodes[next].x = right;
+                self.nodes[next].width = node_right - right;
+                break;
+            }
+        }
+
+        // Merge neighbours that ended up at the same height.
+        let mut i = 0;
+        while i + 1 < self.nodes.len() {
+            if self.nodes[i].y == self.nodes[i + 1].y {
+                self.nodes[i].width += self.nodes[i + 1].width;
+                self.nodes.remove(i + 1);
+            } else {
+                i += 1;
+            }
+        }
+    }
+}
+
+/// The outcome of packing: one placement per input size (in input order) and the
+/// dimensions shared by every page.
+#[derive(Debug, Clone)]
+pub struct PackResult {
+    pub placements: Vec<Placement>,
+    pub page_count: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+fn check_sizes(sizes: &[(u32, u32)], config: &PackerConfig) -> Result<(), usize> {
+    let border = config.extrude * 2 + config.padding;
+    match sizes.iter().position(|&(width, height)| width + border > config.max_size || height + border > config.max_size) {
+        Some(index) => Err(index),
+        None => Ok(()),
+    }
+}
+
+/// Tallest first, then widest, which keeps the skyline flat. The sort is stable so
+/// equally sized sprites stay in input order.
+fn packing_order(sizes: &[(u32, u32)], indices: impl Iterator<Item = usize>) -> Vec<usize> {
+    let mut order: Vec<usize> = indices.collect();
+    order.sort_by(|&a, &b| sizes[b].1.cmp(&sizes[a].1).then(sizes[b].0.cmp(&sizes[a].0)));
+    order
+}
+
+fn page_size(used_width: u32, used_height: u32, config: &PackerConfig) -> (u32, u32) {
+    let (width, height) = if config.power_of_two {
+        (used_width.next_power_of_two(), used_height.next_power_of_two())
+    } else {
+        (used_width, used_height)
+    };
+    (width.min(config.max_size), height.min(config.max_size))
+}
+
+/// Packs sprites of the given sizes onto as many pages as needed.
+///
+/// Returns the index of the first sprite that can never fit if one is larger than
+/// a whole page.
+pub fn pack(sizes: &[(u32, u32)], config: &PackerConfig) -> Result<PackResult, usize> {
+    check_sizes(sizes, config)?;
+    let border = config.extrude * 2 + config.padding;
+
+    let mut placements = vec![Placement { page: 0, x: 0, y: 0 }; sizes.len()];
+    let mut pending = packing_order(sizes, 0..sizes.len());
+    let mut page = 0;
+    let mut used_width = 1;
+    let mut used_height = 1;
+
+    while !pending.is_empty() {
+        let mut skyline = Skyline::new(config.max_size, config.max_size);
+        let mut leftover = Vec::new();
+
+        for index in pending {
+            let (width, height) = sizes[index];
+            match skyline.insert(width + border, height + border) {
+                Some((x, y)) => placements[index] = Placement {
+                    page,
+                    x: x + config.extrude,
+                    y: y + config.extrude,
+                },
+                None => leftover.push(index),
+            }
+        }
+
+        used_width = used_width.max(skyline.used_width);
+        used_height = used_height.max(skyline.used_height);
+        pending = leftover;
+        page += 1;
+    }
+
+    let (width, height) = page_size(used_width, used_height, config);
+    Ok(PackResult {
+        placements,
+        page_count: page.max(1),
+        width,
+        height,
+    })
+}
+
+/// A sprite's slot on a page: the sprite plus its extrusion border and padding.
+#[derive(Debug, Clone, Copy)]
+struct Slot {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+impl Slot {
+    fn overlaps(&self, other: &Slot) -> bool {
+        self.x < other.x + other.width
+            && other.x < self.x + self.width
+            && self.y < other.y + other.height
+            && other.y < self.y + self.height
+    }
+}
+
+/// Finds the top-most, then left-most, free position for a slot of the given size
+/// by trying the corners of the slots already on the page.
+fn find_free_position(occupied: &[Slot], width: u32, height: u32, max_size: u32) -> Option<(u32, u32)> {
+    let mut candidates = vec![(0, 0)];
+    for slot in occupied {
+        candidates.push((slot.x + slot.width, slot.y));
+        candidates.push((slot.x, slot.y + slot.height));
+    }
+    candidates.sort_by_key(|&(x, y)| (y, x));
+
+    candidates.into_iter().find(|&(x, y)| {
+        let candidate = Slot { x, y, width, height };
+        x + width <= max_size && y + height <= max_size && !occupied.iter().any(|slot| slot.overlaps(&candidate))
+    })
+}
+
+/// Packs sprites while keeping every sprite that has a `previous` placement in its
+/// old slot when it still fits there, so unchanged sprites do not move between builds.
+///
+/// New sprites, and old ones whose slot is no longer usable, fill the gaps; pages
+/// left without any sprite are dropped.
+pub fn pack_preserving(sizes: &[(u32, u32)], previous: &[Option<Placement>], config: &PackerConfig) -> Result<PackResult, usize> {
+    check_sizes(sizes, config)?;
+    let border = config.extrude * 2 + config.padding;
+    let slot_of = |index: usize, x: u32, y: u32| Slot {
+        x,
+        y,
+        width: sizes[index].0 + border,
+        height: sizes[index].1 + border,
+    };
+
+    let mut pages: Vec<Vec<Slot>> = Vec::new();
+    let mut placements: Vec<Option<Placement>> = vec![None; sizes.len()];
+
+    for (index, placement) in previous.iter().enumerate() {
+        let Some(placement) = placement else {
+            continue;
+        };
+        if placement.x < config.extrude || placement.y < config.extrude {
+            continue;
+        }
+
+        let slot = slot_of(index, placement.x - config.extrude, placement.y - config.extrude);
+        let page = placement.page as usize;
+        if pages.len() <= page {
+            pages.resize(page + 1, Vec::new());
+        }
+        if slot.x + slot.width <= config.max_size
+            && slot.y + slot.height <= config.max_size
+            && !pages[page].iter().any(|other| other.overlaps(&slot))
+        {
+            pages[page].push(slot);
+            placements[index] = Some(*placement);
+        }
+    }
+
+    let pending = packing_order(sizes, (0..sizes.len()).filter(|&index| placements[index].is_none()));
+    for index in pending {
+        let (width, height) = (sizes[index].0 + border, sizes[index].1 + border);
+        let found = pages
+            .iter()
+            .enumerate()
+            .find_map(|(page, occupied)| find_free_position(occupied, width, height, config.max_size).map(|position| (page, position)));
+
+        let (page, (x, y)) = found.unwrap_or_else(|| {
+            pages.push(Vec::new());
+            (pages.len() - 1, (0, 0))
+        });
+        pages[page].push(slot_of(index, x, y));
+        placements[index] = Some(Placement {
+            page: page as u32,
+            x: x + config.extrude,
+            y: y + config.extrude,
+        });
+    }
+
+    // Renumber pages so that emptied ones do not leave holes.
+    let mut page_numbers = vec![None; pages.len()];
+    let mut page_count = 0;
+    for (page, occupied) in pages.iter().enumerate() {
+        if !occupied.is_empty() {
+            page_numbers[page] = Some(page_count);
+            page_count += 1;
+        }
+    }
+
+    let mut used_width = 1;
+    let mut used_height = 1;
+    for slot in pages.iter().flatten() {
+        used_width = used_width.max(slot.x + slot.width);
+        used_height = used_height.max(slot.y + slot.height);
+    }
+
+    let placements = placements
+        .into_iter()
+        .map(|placement| {
+            let mut placement = placement.expect("every sprite has been placed");
+            placement.page = page_numbers[placement.page as usize].expect("placed sprites keep their page");
+            placement
+        })
+        .collect();
+
+    let (width, height) = page_size(used_width, used_height, config);
+    Ok(PackResult {
+        placements,
+        page_count: page_count.max(1),
+        width,
+        height,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(max_size: u32) -> PackerConfig {
+        PackerConfig {
+            max_size,
+            ..PackerConfig::default()
+        }
+    }
+
+    /// The page and slot, border and padding included, of every placed sprite.
+    fn slots(result: &PackResult, sizes: &[(u32, u32)], config: &PackerConfig) -> Vec<(u32, Slot)> {
+        let border = config.extrude * 2 + config.padding;
+        result.placements.iter().zip(sizes).map(|(placement, &(width, height))| {
+            (placement.page, Slot {
+                x: placement.x - config.extrude,
+                y: placement.y - config.extrude,
+                width: width + border,
+                height: height + border,
+            })
+        }).collect()
+    }
+
+    fn assert_packed(result: &PackResult, sizes: &[(u32, u32)], config: &PackerConfig) {
+        let slots = slots(result, sizes, config);
+        for (index, (page, slot)) in slots.iter().enumerate() {
+            assert!(*page < result.page_count, "sprite {index} is on page {page} of {}", result.page_count);
+            assert!(slot.x + slot.width <= config.max_size && slot.y + slot.height <= config.max_size, "sprite {index} is off the page");
+            for (other_index, (other_page, other)) in slots.iter().enumerate().skip(index + 1) {
+                assert!(page != other_page || !slot.overlaps(other), "sprites {index} and {other_index} overlap");
+            }
+        }
+    }
+
+    fn sizes() -> Vec<(u32, u32)> {
+        vec![(30, 10), (12, 40), (25, 25), (8, 8), (50, 6), (16, 16), (5, 33), (40, 12)]
+    }
+
+    #[test]
+    fn sprites_do_not_overlap() {
+        let config = config(128);
+        let sizes = sizes();
+        let result = pack(&sizes, &config).unwrap();
+        assert_eq!(result.page_count, 1);
+        assert_packed(&result, &sizes, &config);
+    }
+
+    #[test]
+    fn overflow_goes_to_more_pages() {
+        // With the border, four 20 by 20 sprites fill a 48 by 48 page.
+        let config = config(48);
+        let sizes = vec![(20, 20); 9];
+        let result = pack(&sizes, &config).unwrap();
+        assert_eq!(result.page_count, 3);
+        assert_packed(&result, &sizes, &config);
+    }
+
+    #[test]
+    fn oversized_sprite_is_an_error() {
+        let config = config(64);
+        let sizes = [(10, 10), (20, 62), (70, 10)];
+        assert_eq!(pack(&sizes, &config).unwrap_err(), 1);
+        assert_eq!(pack_preserving(&sizes, &[None; 3], &config).unwrap_err(), 1);
+    }
+
+    #[test]
+    fn adding_a_sprite_keeps_the_others_in_place() {
+        let config = config(128);
+        let mut sizes = sizes();
+        let before = pack(&sizes, &config).unwrap();
+
+        sizes.push((20, 20));
+        let previous: Vec<Option<Placement>> = before.placements.iter().copied().map(Some).chain([None]).collect();
+        let after = pack_preserving(&sizes, &previous, &config).unwrap();
+        assert_eq!(after.placements[..before.placements.len()], before.placements[..]);
+        assert_packed(&after, &sizes, &config);
+    }
+}
@@ -1,16 +1,85 @@
-use lib_core::{run, types::Propogate, user_interface::interface::GraphicsInterface};
+use lib_core::{run_with_state, types::Propogate, user_interface::{action::ActionContext, elements::{Anchor, ButtonState, Framed, Label, StateStyle}, input::{KeyChord, KeyCode, ModifiersState}, interface::GraphicsInterface}};
+
+/// Number of times the main button has been clicked.
+struct ClickCount(u32);
 
 fn main() {
     let mut interface = GraphicsInterface::new();
 
     interface.show(|ui| {
-        ui.add_panel([0.0, 0.0, 1.0], [0.5, 0.5]);
-        //ui.add_button([0.0, 0.0, 1.0], [0.25, 0.25], test);
+        ui.add_panel([0.0, 0.0, 1.0], [400.0, 300.0]).skin("panel").color([0.2, 0.3, 0.8, 1.0]);
+        ui.add_panel([80.0, 60.0, 2.0], [160.0, 120.0]).color([0.9, 0.6, 0.1, 1.0]).opacity(0.75).corner_radius(12.0);
+        let counter = ui.add_label([520.0, 540.0, 2.0], "Clicks: 0", 24.0, [1.0, 1.0, 1.0, 1.0]).id();
+        ui.add_button([500.0, 375.0, 1.0], [200.0, 150.0], move |context: &mut ActionContext| {
+            let count = context.state::<ClickCount>();
+            count.0 += 1;
+            let text = format!("Clicks: {}", count.0);
+            if let Some(label) = context.graphics_interface.get_mut::<Label>(counter) {
+                label.set_text(&text);
+            }
+            Propogate::Ok
+        })
+            .skin("button")
+            .color([0.3, 0.8, 0.4, 1.0])
+            .style(ButtonState::Hovered, StateStyle { color: Some([0.4, 0.9, 0.9, 1.0]), ..Default::default() });
+        ui.add_button([500.0, 300.0, 1.0], [200.0, 60.0], test).skin("button").disabled(true);
+        ui.add_icon([-16.0, 16.0, 1.0], [32.0, 32.0], "close").anchor(Anchor::TopRight).pivot(Anchor::TopRight);
+        ui.add_circle([280.0, 420.0, 1.0], [60.0, 60.0]).color([0.9, 0.2, 0.2, 1.0]);
+        ui.add_triangle([440.0, 450.0, 1.0], [60.0, 60.0]).pivot(Anchor::Center).rotation(std::f32::consts::FRAC_PI_6);
+        ui.add_rounded_rectangle([520.0, 210.0, 1.0], [160.0, 60.0], 16.0).color([0.6, 0.6, 0.6, 1.0]);
+        ui.add_polyline(&[[240.0, 540.0], [320.0, 570.0], [400.0, 540.0], [480.0, 570.0]], 3.0, [1.0, 1.0, 0.0, 1.0]);
+        let greeting = ui.add_label([40.0, 30.0, 1.0], "Hello, world!", 32.0, [1.0, 1.0, 1.0, 1.0]).id();
+
+        ui.add_text_input([40.0, 72.0, 2.0], [240.0, 36.0])
+            .placeholder("Your name")
+            .on_submit(move |context: &mut ActionContext, name: &str| {
+                if let Some(label) = context.graphics_interface.get_mut::<Label>(greeting) {
+                    label.set_text(&format!("Hello, {name}!"));
+                }
+                Propogate::Ok
+            });
+
+        // A list longer than the area it is shown in, scrolled with the wheel or a finger.
+        ui.add_scroll_view([40.0, 330.0, 2.0], [160.0, 180.0], |ui| {
+            for row in 0..20 {
+                let y = 336.0 + row as f32 * 30.0;
+                ui.add_button([48.0, y, 2.0], [136.0, 24.0], test);
+                ui.add_label([56.0, y + 3.0, 2.0], &format!("Item {row}"), 18.0, [1.0, 1.0, 1.0, 1.0]);
+            }
+        });
     });
 
-    run(interface).unwrap();
+    // Ctrl+R resets the counter, on top of the default tab, enter and escape bindings.
+    interface.key_map_mut().bind_action(KeyChord::new(KeyCode::KeyR).with(ModifiersState::CONTROL), |context: &mut ActionContext| {
+        context.state::<ClickCount>().0 = 0;
+        Propogate::Ok
+    });
+
+    // The toolbar is described in a document, so it can change without recompiling; its
+    // buttons run the handler registered as "tool".
+    interface.load_document("ui/toolbar.ron");
+    interface.register_handler("tool", test);
+
+    // The look comes from the theme files; ctrl+T swaps between the dark and light ones.
+    interface.load_theme("themes/dark.ron");
+    let mut dark = true;
+    interface.key_map_mut().bind_action(KeyChord::new(KeyCode::KeyT).with(ModifiersState::CONTROL), move |context: &mut ActionContext| {
+        dark = !dark;
+        context.graphics_interface.load_theme(if dark { "themes/dark.ron" } else { "themes/light.ron" });
+        Propogate::Ok
+    });
+
+    interface.on_frame(|ui| {
+        let clicks = ui.state::<ClickCount>().0;
+        ui.label(&format!("Immediate clicks: {clicks}"));
+        if ui.button("Reset") {
+            ui.state::<ClickCount>().0 = 0;
+        }
+    });
+
+    run_with_state(interface, ClickCount(0)).unwrap();
 }
 
-fn test() -> Propogate {
-    return Propogate::Ok;
-}
\ No newline at end of file
+fn test(_context: &mut ActionContext) -> Propogate {
+    Propogate::Ok
+}
@@ -1,17 +1,26 @@
-use std::sync::Arc;
+use std::{any::Any, sync::Arc};
 
 use lib_render::State;
-use winit::{application::ApplicationHandler, event::{KeyEvent, MouseButton, WindowEvent}, event_loop::{ActiveEventLoop, EventLoop}, keyboard::PhysicalKey, window::Window};
-pub use::lib_render::types;
+use winit::{application::ApplicationHandler, dpi::{LogicalPosition, LogicalSize, PhysicalPosition}, event::{KeyEvent, MouseButton, MouseScrollDelta, WindowEvent}, event_loop::{ActiveEventLoop, EventLoop}, keyboard::{ModifiersState, PhysicalKey}, window::Window};
+pub mod types;
 pub mod user_interface;
 
 
 #[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::*;
 
-use crate::user_interface::interface::GraphicsInterface;
+use crate::user_interface::{input::KeyInput, interface::GraphicsInterface};
+
+/// Logical pixels scrolled per line of a mouse wheel.
+const SCROLL_LINE_HEIGHT: f32 = 40.0;
 
 pub fn run(graphics_interface: GraphicsInterface) -> anyhow::Result<()> {
+    run_with_state(graphics_interface, ())
+}
+
+/// Runs the application with a state that element actions can reach through
+/// [`ActionContext::state`](user_interface::action::ActionContext::state).
+pub fn run_with_state(graphics_interface: GraphicsInterface, app_state: impl Any) -> anyhow::Result<()> {
     #[cfg(not(target_arch = "wasm32"))]
     {
         env_logger::init();
@@ -24,6 +33,7 @@ pub fn run(graphics_interface: GraphicsInterface) -> anyhow::Result<()> {
     let event_loop = EventLoop::with_user_event().build()?;
     let mut app = App::new(
         graphics_interface,
+        Box::new(app_state),
         #[cfg(target_arch = "wasm32")]
         &event_loop,
     );
@@ -40,15 +50,26 @@ pub fn run_web(graphics_interface: GraphicsInterface) -> Result<(), wasm_bindgen
     Ok(())
 }
 
+/// The cursor position in logical pixels.
+fn logical_cursor(position: PhysicalPosition<f64>, state: &State) -> [f32; 2] {
+    [position.x as f32 / state.scale_factor(), position.y as f32 / state.scale_factor()]
+}
+
 pub struct App {
     #[cfg(target_arch = "wasm32")]
     proxy: Option<winit::event_loop::EventLoopProxy<State>>,
     state: Option<State>,
     graphics_interface: GraphicsInterface,
+    app_state: Box<dyn Any>,
+    /// Last cursor position reported by the window, `None` while it is outside.
+    cursor_position: Option<PhysicalPosition<f64>>,
+    modifiers: ModifiersState,
+    /// Centre and size in logical pixels of the focused text field the input method is enabled for.
+    text_input_area: Option<([f32; 2], [f32; 2])>,
 }
 
 impl App {
-    pub fn new(graphics_interface: GraphicsInterface, #[cfg(target_arch = "wasm32")] event_loop: &EventLoop<State>) -> Self {
+    pub fn new(graphics_interface: GraphicsInterface, app_state: Box<dyn Any>, #[cfg(target_arch = "wasm32")] event_loop: &EventLoop<State>) -> Self {
         #[cfg(target_arch = "wasm32")]
         let proxy = Some(event_loop.create_proxy());
         Self {
@@ -56,6 +77,10 @@ impl App {
             #[cfg(target_arch = "wasm32")]
             proxy,
             graphics_interface,
+            app_state,
+            cursor_position: None,
+            modifiers: ModifiersState::empty(),
+            text_input_area: None,
         }
     }
 }
@@ -76,10 +101,13 @@ impl ApplicationHandler<State> for App {
             let document = window.document().unwrap_throw();
             let canvas = document.get_element_by_id(CANVAS_ID).unwrap_throw();
             let html_canvas_element = canvas.unchecked_into();
-            window_attributes = window_attributes.with_canvas(Some(html_canvas_element));
+            // Focusable so that the canvas receives keyboard input for text fields.
+            window_attributes = window_attributes.with_canvas(Some(html_canvas_element)).with_focusable(true);
         }
 
         let window = Arc::new(event_loop.create_window(window_attributes).unwrap());
+        let size = window.inner_size();
+        self.graphics_interface.relayout([size.width.max(1) as f32, size.height.max(1) as f32], window.scale_factor() as f32);
         let staged_ui_data = self.graphics_interface.stage();
 
         #[cfg(not(target_arch = "wasm32"))]
@@ -110,10 +138,9 @@ impl ApplicationHandler<State> for App {
         #[cfg(target_arch = "wasm32")]
         {
             event.window.request_redraw();
-            event.resize(
-                event.window.inner_size().width,
-                event.window.inner_size().height,
-            );
+            let size = event.window.inner_size();
+            event.resize(size.width, size.height);
+            self.graphics_interface.relayout([size.width.max(1) as f32, size.height.max(1) as f32], event.scale_factor());
         }
         self.state = Some(event);
     }
@@ -131,8 +158,26 @@ impl ApplicationHandler<State> for App {
 
         match event {
             WindowEvent::CloseRequested => event_loop.exit(),
-            WindowEvent::Resized(size) => state.resize(size.width, size.height),
+            WindowEvent::Resized(size) => {
+                state.resize(size.width, size.height);
+                self.graphics_interface.relayout([size.width.max(1) as f32, size.height.max(1) as f32], state.scale_factor());
+            }
+            WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                // The new physical size follows in a `Resized` event, if it changes.
+                state.set_scale_factor(scale_factor);
+                let size = state.window.inner_size();
+                self.graphics_interface.relayout([size.width.max(1) as f32, size.height.max(1) as f32], scale_factor as f32);
+            }
             WindowEvent::RedrawRequested => {
+                self.graphics_interface.process_commands();
+                if let Some(update) = self.graphics_interface.take_update() {
+                    state.update_stage(update);
+                }
+                let size = state.window.inner_size();
+                let window_size = [size.width.max(1) as f32, size.height.max(1) as f32];
+                if let Some(stage) = self.graphics_interface.run_immediate(window_size, state.scale_factor(), self.app_state.as_mut()) {
+                    state.set_immediate_stage(stage);
+                }
                 state.update();
                 match state.render() {
                     Ok(_) => {}
@@ -146,21 +191,97 @@ impl ApplicationHandler<State> for App {
                     }
                 }
             }
-            WindowEvent::MouseInput { state, button, .. } => match (button, state.is_pressed()) {
-                (MouseButton::Left, true) => {}
-                (MouseButton::Left, false) => {}
-                _ => {}
-            },
+            WindowEvent::CursorMoved { position, .. } => {
+                self.cursor_position = Some(position);
+                let point = logical_cursor(position, state);
+                self.graphics_interface.pointer_moved(Some(point));
+                if let Some(immediate) = self.graphics_interface.immediate_mut() {
+                    immediate.pointer_moved(Some(point));
+                }
+            }
+            WindowEvent::CursorLeft { .. } => {
+                self.cursor_position = None;
+                self.graphics_interface.pointer_moved(None);
+                if let Some(immediate) = self.graphics_interface.immediate_mut() {
+                    immediate.pointer_moved(None);
+                }
+            }
+            WindowEvent::MouseInput { state: button_state, button: MouseButton::Left, .. } => {
+                // Immediate-mode widgets are drawn over the retained interface, so they get the click first.
+                let immediate_wants_pointer = self.graphics_interface.immediate_mut().is_some_and(|immediate| {
+                    let wants_pointer = immediate.wants_pointer();
+                    immediate.pointer_button(button_state.is_pressed());
+                    wants_pointer
+                });
+                let Some(position) = self.cursor_position else {
+                    return;
+                };
+                let point = logical_cursor(position, state);
+                // Releases always reach the retained interface, so a press it took is never left hanging.
+                match button_state.is_pressed() {
+                    true if !immediate_wants_pointer => self.graphics_interface.pointer_pressed(point),
+                    true => false,
+                    false => self.graphics_interface.pointer_released(point, self.app_state.as_mut()),
+                };
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                let Some(position) = self.cursor_position else {
+                    return;
+                };
+                let point = logical_cursor(position, state);
+                let delta = match delta {
+                    MouseScrollDelta::LineDelta(x, y) => [x, y].map(|lines| lines * SCROLL_LINE_HEIGHT),
+                    MouseScrollDelta::PixelDelta(delta) => [delta.x as f32, delta.y as f32].map(|pixels| pixels / state.scale_factor()),
+                };
+                // The wheel moves the content, so the view's offset moves the other way.
+                self.graphics_interface.scroll(point, [-delta[0], -delta[1]]);
+            }
+            WindowEvent::Touch(touch) => {
+                let point = logical_cursor(touch.location, state);
+                self.graphics_interface.touch(touch.id, touch.phase, point);
+            }
+            WindowEvent::ModifiersChanged(modifiers) => self.modifiers = modifiers.state(),
+            WindowEvent::Ime(event) => {
+                self.graphics_interface.ime_input(&event);
+            }
             WindowEvent::KeyboardInput {
                 event:
                     KeyEvent {
                         physical_key: PhysicalKey::Code(code),
+                        logical_key,
+                        text,
                         state: key_state,
+                        repeat,
                         ..
                     },
                 ..
-            } => state.handle_key(event_loop, code, key_state.is_pressed()),
+            } => {
+                let input = KeyInput {
+                    code,
+                    key: logical_key,
+                    text: text.map(|text| text.to_string()),
+                    pressed: key_state.is_pressed(),
+                    repeat,
+                    modifiers: self.modifiers,
+                };
+                self.graphics_interface.key_input(&input, self.app_state.as_mut());
+            }
             _ => {}
         }
+
+        let text_input_area = self.graphics_interface.text_input_area();
+        if text_input_area != self.text_input_area {
+            state.window.set_ime_allowed(text_input_area.is_some());
+            if let Some((centre, scale)) = text_input_area {
+                // Tells the input method where to show its candidate window.
+                let [x, y] = [0, 1].map(|axis| centre[axis] - scale[axis] / 2.0);
+                state.window.set_ime_cursor_area(LogicalPosition::new(x, y), LogicalSize::new(scale[0], scale[1]));
+            }
+            self.text_input_area = text_input_area;
+        }
+
+        if self.graphics_interface.take_exit_request() {
+            event_loop.exit();
+        }
     }
 }
\ No newline at end of file
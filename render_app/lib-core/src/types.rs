@@ -0,0 +1,94 @@
+use std::any::Any;
+
+pub use lib_render::types::*;
+
+use crate::user_interface::{action::ActionContext, input::{Ime, KeyInput}, theme::Theme};
+
+pub trait Element: Any {
+    fn geometry(&self) -> GeometryType;
+    fn as_instance(&self) -> Instance;
+    /// Name of the atlas entry to draw this element with.
+    fn sprite(&self) -> Option<&str> {
+        None
+    }
+    /// Width in logical pixels of a [`GeometryType::Line`] element.
+    fn line_width(&self) -> Option<f32> {
+        None
+    }
+    /// Whether `point`, in logical pixels, falls inside the element's rotated rect.
+    fn contains(&self, point: [f32; 2]) -> bool {
+        let instance = self.as_instance();
+        let offset = [point[0] - instance.position[0], point[1] - instance.position[1]];
+        let (sin, cos) = instance.rotation.sin_cos();
+        let local = [offset[0] * cos + offset[1] * sin, offset[1] * cos - offset[0] * sin];
+        let half_size = [instance.scale[0].abs() / 2.0, instance.scale[1].abs() / 2.0];
+        local[0].abs() <= half_size[0] && local[1].abs() <= half_size[1]
+    }
+    /// Solid quads drawn right over the element, with positions and scales in logical pixels.
+    fn overlays(&self) -> Vec<Instance> {
+        Vec::new()
+    }
+    /// Text drawn with the element, ordered by depth with the elements: over those at its
+    /// depth or below and under those above it.
+    fn text(&self) -> Option<TextSection> {
+        None
+    }
+    /// Told the size of the window in logical pixels whenever it changes, for elements
+    /// anchored to a point of the window other than its top-left corner.
+    fn set_viewport(&mut self, _viewport: [f32; 2]) {}
+    /// Moves the element's centre to `position` and resizes it to `scale`, in logical
+    /// pixels, as resolved by a layout. Elements that can't be resized ignore it.
+    fn place(&mut self, _position: [f32; 2], _scale: [f32; 2]) {}
+    /// Restyles the element with its style in `theme`, keeping what was set on it directly.
+    fn apply_theme(&mut self, _theme: &Theme) {}
+    fn as_clickable(&self) -> Option<&dyn Clickable> {
+        None
+    }
+    fn as_clickable_mut(&mut self) -> Option<&mut dyn Clickable> {
+        None
+    }
+}
+
+/// How the pointer and keyboard are currently interacting with an element.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct InteractionState {
+    /// The pointer is over the element.
+    pub hovered: bool,
+    /// The element was pressed and the press hasn't been released yet.
+    pub pressed: bool,
+    /// The element has keyboard focus.
+    pub focused: bool,
+}
+
+pub trait Clickable {
+    fn click(&mut self, context: &mut ActionContext) -> Propogate;
+    /// Called whenever the element's interaction state changes, so it can restyle itself.
+    fn set_interaction(&mut self, _state: InteractionState) {}
+    /// Disabled elements are neither pressed nor clicked, and don't pass clicks on.
+    fn is_disabled(&self) -> bool {
+        false
+    }
+    /// Called with every key while the element has the focus, before the key map sees it.
+    fn key(&mut self, _input: &KeyInput, _context: &mut ActionContext) -> Propogate {
+        Propogate::Propogate
+    }
+    /// Called when the pointer is pressed on the element, with the point in logical pixels.
+    fn pointer_pressed(&mut self, _point: [f32; 2]) {}
+    /// Called when the pointer moves while it is pressed on the element, with the point in logical pixels.
+    fn pointer_dragged(&mut self, _point: [f32; 2]) {}
+    /// Whether the element takes text while it has the focus, which turns on the input method.
+    fn accepts_text(&self) -> bool {
+        false
+    }
+    /// Called with input method events while the element has the focus.
+    fn ime(&mut self, _event: &Ime) {}
+}
+
+/// What a [`Clickable`] does with the click it received.
+#[derive(Debug)]
+pub enum Propogate {
+    /// Pass the click on to the next clickable element underneath.
+    Propogate,
+    /// The click was handled; elements underneath don't see it.
+    Ok
+}
\ No newline at end of file
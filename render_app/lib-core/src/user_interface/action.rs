@@ -0,0 +1,72 @@
+use std::any::{Any, type_name};
+
+use crate::{types::Propogate, user_interface::interface::{ElementId, GraphicsInterface}};
+
+/// What an element's action is run with: the application's state and the interface the
+/// element belongs to.
+///
+/// The element running the action is taken out of the interface for the duration of
+/// the call, so it won't show up when walking the interface's elements.
+pub struct ActionContext<'a> {
+    pub graphics_interface: &'a mut GraphicsInterface,
+    app_state: &'a mut dyn Any,
+    element: Option<ElementId>,
+}
+
+impl<'a> ActionContext<'a> {
+    pub(crate) fn new(graphics_interface: &'a mut GraphicsInterface, app_state: &'a mut dyn Any) -> Self {
+        Self {
+            graphics_interface,
+            app_state,
+            element: None,
+        }
+    }
+
+    pub(crate) fn for_element(graphics_interface: &'a mut GraphicsInterface, app_state: &'a mut dyn Any, element: ElementId) -> Self {
+        Self {
+            element: Some(element),
+            ..Self::new(graphics_interface, app_state)
+        }
+    }
+
+    /// The element the action belongs to, `None` for actions bound in the key map.
+    pub fn element(&self) -> Option<ElementId> {
+        self.element
+    }
+
+    /// The state the application was started with, see [`crate::run_with_state`].
+    ///
+    /// Panics if the state is not a `T`.
+    pub fn state<T: Any>(&mut self) -> &mut T {
+        match self.app_state.downcast_mut() {
+            Some(state) => state,
+            None => panic!("the application state is not a {}", type_name::<T>()),
+        }
+    }
+
+    /// The state the application was started with, or `None` if it is not a `T`.
+    pub fn try_state<T: Any>(&mut self) -> Option<&mut T> {
+        self.app_state.downcast_mut()
+    }
+
+    /// Runs the handler registered as `name` with [`GraphicsInterface::register_handler`],
+    /// passing the click on if there is none.
+    pub fn run_handler(&mut self, name: &str) -> Propogate {
+        let Some(mut handler) = self.graphics_interface.take_handler(name) else {
+            log::warn!("No handler is registered as {:?}", name);
+            return Propogate::Propogate;
+        };
+        let propogate = handler(self);
+        self.graphics_interface.restore_handler(name, handler);
+        propogate
+    }
+}
+
+/// A callback run when an element is activated, e.g. a [`Button`](super::elements::Button) is clicked.
+pub type Action = Box<dyn FnMut(&mut ActionContext) -> Propogate>;
+
+/// A callback run with the text of a [`TextInput`](super::text_input::TextInput) when it is submitted.
+///
+/// The field is taken out of the interface while the callback runs, so it is handed the text
+/// rather than finding the field by its ID.
+pub type SubmitAction = Box<dyn FnMut(&mut ActionContext, &str) -> Propogate>;
@@ -0,0 +1,96 @@
+//! Text copied to and pasted from the system clipboard.
+//!
+//! Reading is asynchronous in the browser, so pasted text is handed over through the
+//! interface's command queue on every platform.
+
+use std::sync::mpsc::Sender;
+
+use crate::user_interface::interface::{GraphicsInterface, UiCommand};
+
+#[cfg(not(target_arch = "wasm32"))]
+mod platform {
+    use std::cell::RefCell;
+
+    thread_local! {
+        // Kept for as long as the application runs, as on X11 the copied text is served
+        // by the clipboard that copied it.
+        static CLIPBOARD: RefCell<Option<arboard::Clipboard>> = const { RefCell::new(None) };
+    }
+
+    fn with_clipboard<R>(f: impl FnOnce(&mut arboard::Clipboard) -> Result<R, arboard::Error>) -> Option<R> {
+        CLIPBOARD.with_borrow_mut(|clipboard| {
+            if clipboard.is_none() {
+                match arboard::Clipboard::new() {
+                    Ok(opened) => *clipboard = Some(opened),
+                    Err(error) => {
+                        log::warn!("The clipboard is unavailable: {}", error);
+                        return None;
+                    }
+                }
+            }
+            f(clipboard.as_mut()?)
+                .map_err(|error| log::debug!("Clipboard access failed: {}", error))
+                .ok()
+        })
+    }
+
+    pub(super) fn write(text: &str) {
+        with_clipboard(|clipboard| clipboard.set_text(text));
+    }
+
+    pub(super) fn read(receive: impl FnOnce(String) + 'static) {
+        if let Some(text) = with_clipboard(|clipboard| clipboard.get_text()) {
+            receive(text);
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+mod platform {
+    use wasm_bindgen_futures::{JsFuture, spawn_local};
+
+    fn clipboard() -> Option<web_sys::Clipboard> {
+        Some(web_sys::window()?.navigator().clipboard())
+    }
+
+    pub(super) fn write(text: &str) {
+        let Some(clipboard) = clipboard() else {
+            return;
+        };
+        let promise = clipboard.write_text(text);
+        spawn_local(async move {
+            if let Err(error) = JsFuture::from(promise).await {
+                log::warn!("Couldn't copy to the clipboard: {:?}", error);
+            }
+        });
+    }
+
+    pub(super) fn read(receive: impl FnOnce(String) + 'static) {
+        let Some(clipboard) = clipboard() else {
+            return;
+        };
+        let promise = clipboard.read_text();
+        spawn_local(async move {
+            match JsFuture::from(promise).await {
+                Ok(text) => {
+                    if let Some(text) = text.as_string() {
+                        receive(text);
+                    }
+                }
+                Err(error) => log::warn!("Couldn't paste from the clipboard: {:?}", error),
+            }
+        });
+    }
+}
+
+pub(crate) fn write(text: &str) {
+    platform::write(text);
+}
+
+/// Reads the clipboard and queues `paste` on `commands` with its text.
+pub(crate) fn read(commands: Sender<UiCommand>, paste: impl FnOnce(&mut GraphicsInterface, String) + Send + 'static) {
+    platform::read(move |text| {
+        // The interface is gone if the application is closing.
+        let _ = commands.send(Box::new(move |graphics_interface| paste(graphics_interface, text)));
+    });
+}
@@ -0,0 +1,273 @@
+//! Screens of the user interface described as data, so they can change without recompiling.
+//!
+//! A [`UiDocument`] is a tree of elements and layout containers. Elements can be given
+//! an `id` to find them by with [`GraphicsInterface::named`], and buttons and text
+//! fields name the handler they run, registered in Rust with
+//! [`GraphicsInterface::register_handler`].
+//!
+//! [`GraphicsInterface::named`]: super::interface::GraphicsInterface::named
+//! [`GraphicsInterface::register_handler`]: super::interface::GraphicsInterface::register_handler
+
+use serde::Deserialize;
+
+use crate::{types::Propogate, user_interface::{UserInterface, action::ActionContext, elements::{Anchor, Framed}, format, layout::{Align, Container, Edges, Item, Justify, Length, Node}, theme::Style}};
+
+/// Font height in logical pixels of a label whose style doesn't give one.
+const LABEL_SIZE: f32 = 18.0;
+const LABEL_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+
+/// A tree of elements and layout containers, see the [module documentation](self).
+///
+/// In RON, a document looks like:
+///
+/// ```ron
+/// (
+///     nodes: [
+///         Panel(id: "background", position: (0.0, 0.0, 1.0), scale: (400.0, 300.0), class: "toolbar"),
+///         Column(padding: (left: 12.0, top: 12.0, right: 12.0, bottom: 12.0), gap: 8.0, children: [
+///             Label(text: "Tools", style: (font_size: 20.0)),
+///             Button(id: "save", action: "save", height: Pixels(32.0)),
+///         ]),
+///     ],
+/// )
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct UiDocument {
+    pub nodes: Vec<DocumentNode>,
+}
+
+/// An element or layout container in a [`UiDocument`].
+///
+/// Elements at the top of the document, or of a scroll view's children, are placed by
+/// their position and scale; those in a container are placed by the container.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub enum DocumentNode {
+    Panel(ElementNode),
+    Button(ElementNode),
+    /// A quad drawn with the UI atlas entry named by its `sprite`.
+    Icon(ElementNode),
+    Label(ElementNode),
+    TextInput(ElementNode),
+    ScrollView(ElementNode),
+    Row(ContainerNode),
+    Column(ContainerNode),
+    Stack(ContainerNode),
+    Grid(ContainerNode),
+}
+
+/// An element in a [`UiDocument`]. Unset fields leave the element as it is built in Rust.
+///
+/// Not every element uses every field: labels show their `text` and text fields their
+/// `text` as placeholder, buttons run their `action` when clicked and text fields when
+/// submitted, icons draw their `sprite`, and scroll views scroll their `children`.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ElementNode {
+    /// The name to find the element by with [`GraphicsInterface::named`](super::interface::GraphicsInterface::named).
+    pub id: Option<String>,
+    /// Position and size in logical pixels, see [`Framed`].
+    pub position: [f32; 3],
+    pub scale: [f32; 2],
+    pub anchor: Anchor,
+    pub pivot: Anchor,
+    /// The theme style to use instead of the element's own, e.g. `"toolbar"`.
+    pub class: Option<String>,
+    /// Styling over the theme style, whatever the theme.
+    pub style: Style,
+    pub text: String,
+    pub sprite: String,
+    /// The name of the handler to run, see [`GraphicsInterface::register_handler`](super::interface::GraphicsInterface::register_handler).
+    pub action: Option<String>,
+    pub disabled: bool,
+    /// Sizing within the parent container, see [`Item`].
+    pub width: Length,
+    pub height: Length,
+    pub min_size: Option<[f32; 2]>,
+    pub max_size: Option<[f32; 2]>,
+    pub grow: f32,
+    pub children: Vec<DocumentNode>,
+}
+
+/// A layout container in a [`UiDocument`], see [`Container`].
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ContainerNode {
+    /// Number of columns of a grid.
+    pub columns: usize,
+    pub width: Length,
+    pub height: Length,
+    pub min_size: Option<[f32; 2]>,
+    pub max_size: Option<[f32; 2]>,
+    pub grow: f32,
+    pub padding: Edges,
+    pub gap: f32,
+    pub align: Align,
+    pub justify: Justify,
+    pub children: Vec<DocumentNode>,
+}
+
+impl UiDocument {
+    /// Loads a document from the resources, in the format its extension names.
+    pub async fn load(file_name: &str) -> anyhow::Result<Self> {
+        format::load(file_name).await
+    }
+
+    /// Adds the document's elements, and its containers as layouts.
+    pub(crate) fn build(&self, ui: &mut UserInterface) {
+        add_nodes(ui, &self.nodes);
+    }
+}
+
+/// Adds `nodes` outside of any container.
+fn add_nodes(ui: &mut UserInterface, nodes: &[DocumentNode]) {
+    for node in nodes {
+        if let Node::Container(container) = add_node(ui, node) {
+            ui.add_layout(container);
+        }
+    }
+}
+
+/// Adds the elements of `node`, returning how its parent container places it.
+fn add_node(ui: &mut UserInterface, node: &DocumentNode) -> Node {
+    let (element, id) = match node {
+        DocumentNode::Row(container) => return Node::Container(container.build(ui, Container::row())),
+        DocumentNode::Column(container) => return Node::Container(container.build(ui, Container::column())),
+        DocumentNode::Stack(container) => return Node::Container(container.build(ui, Container::stack())),
+        DocumentNode::Grid(container) => return Node::Container(container.build(ui, Container::grid(container.columns))),
+        DocumentNode::Panel(element) => {
+            let mut panel = ui.add_panel(element.position, element.scale);
+            element.frame(&mut *panel);
+            (element, panel.id())
+        }
+        DocumentNode::Button(element) => {
+            let mut button = ui.add_button(element.position, element.scale, handler(&element.action));
+            element.frame(&mut *button);
+            button.disabled(element.disabled);
+            (element, button.id())
+        }
+        DocumentNode::Icon(element) => {
+            let mut icon = ui.add_icon(element.position, element.scale, &element.sprite);
+            element.frame(&mut *icon);
+            (element, icon.id())
+        }
+        DocumentNode::Label(element) => {
+            let mut label = ui.add_label(element.position, &element.text, LABEL_SIZE, LABEL_COLOR);
+            element.frame(&mut *label);
+            (element, label.id())
+        }
+        DocumentNode::TextInput(element) => {
+            let mut input = ui.add_text_input(element.position, element.scale);
+            element.frame(&mut *input);
+            input.placeholder(&element.text);
+            if element.action.is_some() {
+                let mut action = handler(&element.action);
+                input.on_submit(move |context, _| action(context));
+            }
+            (element, input.id())
+        }
+        DocumentNode::ScrollView(element) => {
+            let mut view = ui.add_scroll_view(element.position, element.scale, |ui| add_nodes(ui, &element.children));
+            element.frame(&mut *view);
+            (element, view.id())
+        }
+    };
+
+    if let Some(name) = &element.id {
+        ui.graphics_interface.set_name(name, id);
+    }
+    let mut item = Item::new(id).width(element.width).height(element.height).grow(element.grow);
+    if let Some(size) = element.min_size {
+        item = item.min_size(size);
+    }
+    if let Some(size) = element.max_size {
+        item = item.max_size(size);
+    }
+    Node::Item(item)
+}
+
+impl ElementNode {
+    /// Anchors, pivots and styles the element added for this node.
+    fn frame(&self, element: &mut impl Framed) {
+        element.anchor(self.anchor).pivot(self.pivot).style_override(self.style.clone());
+        if let Some(class) = &self.class {
+            element.class(class);
+        }
+    }
+}
+
+impl ContainerNode {
+    /// Adds the elements of the container's children, returning `container` laid out as described.
+    fn build(&self, ui: &mut UserInterface, container: Container) -> Container {
+        let mut container = container
+            .width(self.width)
+            .height(self.height)
+            .grow(self.grow)
+            .padding(self.padding)
+            .gap(self.gap)
+            .align(self.align)
+            .justify(self.justify);
+        if let Some(size) = self.min_size {
+            container = container.min_size(size);
+        }
+        if let Some(size) = self.max_size {
+            container = container.max_size(size);
+        }
+        self.children.iter().fold(container, |container, child| container.child(add_node(ui, child)))
+    }
+}
+
+/// An action running the handler called `name`, looked up when it runs so that handlers
+/// can be registered after the document is added.
+fn handler(name: &Option<String>) -> impl FnMut(&mut ActionContext) -> Propogate + 'static {
+    let name = name.clone();
+    move |context: &mut ActionContext| match &name {
+        Some(name) => context.run_handler(name),
+        None => Propogate::Propogate,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, rc::Rc};
+
+    use super::*;
+    use crate::user_interface::{format::DataFormat, interface::GraphicsInterface};
+
+    const DOCUMENT: &str = r#"(
+        nodes: [
+            Button(id: "save", action: "save", scale: (100.0, 50.0)),
+            TextInput(id: "name", action: "rename", position: (0.0, 100.0, 0.0), scale: (200.0, 30.0)),
+        ],
+    )"#;
+
+    #[test]
+    fn parses_elements_with_their_handler_names() {
+        let document: UiDocument = DataFormat::Ron.parse(DOCUMENT).unwrap();
+        let [DocumentNode::Button(button), DocumentNode::TextInput(input)] = &document.nodes[..] else {
+            panic!("unexpected nodes {:?}", document.nodes);
+        };
+        assert_eq!((button.id.as_deref(), button.action.as_deref()), (Some("save"), Some("save")));
+        assert_eq!(button.scale, [100.0, 50.0]);
+        assert_eq!((input.id.as_deref(), input.action.as_deref()), (Some("name"), Some("rename")));
+    }
+
+    #[test]
+    fn unknown_handlers_pass_the_click_on_until_registered() {
+        let document: UiDocument = DataFormat::Ron.parse(DOCUMENT).unwrap();
+        let mut interface = GraphicsInterface::new();
+        interface.add_document(&document);
+        assert!(interface.named("save").is_some());
+        assert!(!interface.click([50.0, 25.0], &mut ()));
+
+        let saves = Rc::new(Cell::new(0));
+        let counter = saves.clone();
+        interface.register_handler("save", move |_| {
+            counter.set(counter.get() + 1);
+            Propogate::Ok
+        });
+        assert!(interface.click([50.0, 25.0], &mut ()));
+        assert_eq!(saves.get(), 1);
+    }
+}
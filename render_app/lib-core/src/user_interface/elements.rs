@@ -1,19 +1,233 @@
-use lib_render::types::{Clickable, Element, GeometryType, Instance, Propogate};
+use std::collections::HashMap;
 
+use lib_render::gui_backend::measure_text;
+use serde::Deserialize;
+
+use crate::{types::{Clickable, Element, GeometryType, InteractionState, Instance, Propogate, TextSection}, user_interface::{action::{Action, ActionContext}, theme::{Style, Styling, Theme}}};
+
+/// Opaque white, the tint that leaves a sprite unchanged.
+const WHITE: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+
+/// A point on a rect: the point of the window an element's position is measured from
+/// (its anchor), or the point of the element that sits at its position (its pivot).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+pub enum Anchor {
+    #[default]
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl Anchor {
+    /// The point as a fraction of the rect's size, from its top-left corner.
+    pub fn point(self) -> [f32; 2] {
+        match self {
+            Anchor::TopLeft => [0.0, 0.0],
+            Anchor::Top => [0.5, 0.0],
+            Anchor::TopRight => [1.0, 0.0],
+            Anchor::Left => [0.0, 0.5],
+            Anchor::Center => [0.5, 0.5],
+            Anchor::Right => [1.0, 0.5],
+            Anchor::BottomLeft => [0.0, 1.0],
+            Anchor::Bottom => [0.5, 1.0],
+            Anchor::BottomRight => [1.0, 1.0],
+        }
+    }
+}
+
+
+/// Where an element placed as a rect sits and how it looks, set through [`Framed`].
+pub struct Frame {
+    pub(crate) position: [f32; 3],
+    pub(crate) scale: [f32; 2],
+    pub(crate) anchor: Anchor,
+    pub(crate) pivot: Anchor,
+    /// Size of the window in logical pixels, which the anchor is a point of.
+    pub(crate) viewport: [f32; 2],
+    pub(crate) color: [f32; 4],
+    pub(crate) rotation: f32,
+    pub(crate) corner_radius: f32,
+    pub(crate) styling: Styling,
+    /// The color and corner radius used where the theme doesn't style them.
+    pub(crate) default_color: [f32; 4],
+    pub(crate) default_corner_radius: f32,
+}
+
+impl Frame {
+    pub(crate) fn new(position: [f32; 3], scale: [f32; 2], color: [f32; 4], class: &str) -> Self {
+        Self {
+            position,
+            scale,
+            anchor: Anchor::default(),
+            pivot: Anchor::default(),
+            viewport: [0.0; 2],
+            color,
+            rotation: 0.0,
+            corner_radius: 0.0,
+            styling: Styling::new(class),
+            default_color: color,
+            default_corner_radius: 0.0,
+        }
+    }
+
+    /// Restyles the frame's color and corner radius with its style in `theme`, and
+    /// returns the style for the element to take the rest from.
+    pub(crate) fn apply_theme(&mut self, theme: &Theme) -> Style {
+        let style = self.styling.resolve(theme);
+        self.color = theme.color(&style.color).unwrap_or(self.default_color);
+        self.corner_radius = theme.corner_radius(&style.corner_radius).unwrap_or(self.default_corner_radius);
+        style
+    }
+
+    /// The point of the window in logical pixels that the position is measured from.
+    pub(crate) fn anchor_point(&self) -> [f32; 2] {
+        let anchor = self.anchor.point();
+        [anchor[0] * self.viewport[0], anchor[1] * self.viewport[1]]
+    }
+
+    /// The centre of the rect in logical pixels.
+    pub(crate) fn centre(&self) -> [f32; 2] {
+        let (anchor, pivot) = (self.anchor_point(), self.pivot.point());
+        [0, 1].map(|axis| anchor[axis] + self.position[axis] + (0.5 - pivot[axis]) * self.scale[axis])
+    }
+
+    pub(crate) fn depth(&self) -> f32 {
+        self.position[2]
+    }
+
+    /// The rect drawn in the frame's color.
+    pub(crate) fn instance(&self) -> Instance {
+        Instance {
+            position: self.centre(),
+            scale: self.scale,
+            rotation: self.rotation,
+            color: self.color,
+            corner_radius: self.corner_radius,
+            depth: self.depth(),
+            ..Default::default()
+        }
+    }
+
+    /// Moves the rect's centre to `position` and resizes it to `scale`, in logical pixels.
+    pub(crate) fn place(&mut self, position: [f32; 2], scale: [f32; 2]) {
+        self.scale = scale;
+        let (anchor, pivot) = (self.anchor_point(), self.pivot.point());
+        let [x, y] = [0, 1].map(|axis| position[axis] - anchor[axis] - (0.5 - pivot[axis]) * scale[axis]);
+        self.position = [x, y, self.position[2]];
+    }
+}
+
+/// The builder methods shared by the elements with a [`Frame`]: panels, buttons, icons,
+/// shapes, lines, labels, text fields and scroll views.
+///
+/// An element's position is where its pivot, by default its top-left corner, sits relative
+/// to its anchor, by default the top-left corner of the window, and its scale is its size.
+/// Both are in logical pixels, which the renderer scales by the window's scale factor so
+/// that the interface keeps its size on high-DPI displays.
+///
+/// Elements are layered by the `z` of their position: larger values are drawn on top,
+/// and elements with the same `z` in the order they were added.
+pub trait Framed {
+    fn frame_mut(&mut self) -> &mut Frame;
+
+    /// Styles the element with the theme's style called `class` instead of the one named
+    /// after its kind, e.g. `"panel"` or `"text_input"`.
+    fn class(&mut self, class: &str) -> &mut Self {
+        self.frame_mut().styling.set_class(class);
+        self
+    }
+
+    /// Styles the element with the fields set in `style` over its theme style, whatever the theme.
+    fn style_override(&mut self, style: Style) -> &mut Self {
+        let styling = &mut self.frame_mut().styling;
+        styling.overrides = styling.overrides.merged(&style);
+        self
+    }
+
+    /// Measures the position from this point of the window. E.g. with [`Anchor::BottomRight`]
+    /// for both anchor and pivot, a position of `[0.0, 0.0, z]` keeps the element in that
+    /// corner as the window resizes.
+    fn anchor(&mut self, anchor: Anchor) -> &mut Self {
+        self.frame_mut().anchor = anchor;
+        self
+    }
+
+    /// The point of the element that sits at its position. For a label, the point of its text's bounds.
+    fn pivot(&mut self, pivot: Anchor) -> &mut Self {
+        self.frame_mut().pivot = pivot;
+        self
+    }
+
+    /// Sets the element's linear RGBA color: the tint of a panel, button or icon, the fill
+    /// of a shape, the text of a label and the background of a text field or scroll view.
+    fn color(&mut self, color: [f32; 4]) -> &mut Self {
+        let frame = self.frame_mut();
+        frame.color = color;
+        frame.styling.overrides.color = Some(color.into());
+        self
+    }
+
+    /// Sets the alpha of the element's color, from 0 (invisible) to 1 (opaque).
+    fn opacity(&mut self, opacity: f32) -> &mut Self {
+        let frame = self.frame_mut();
+        frame.color[3] = opacity;
+        frame.styling.overrides.color = Some(frame.color.into());
+        self
+    }
+
+    /// Rotates the element clockwise around its centre, in radians. Lines, labels, text
+    /// fields and scroll views aren't rotated.
+    fn rotation(&mut self, rotation: f32) -> &mut Self {
+        self.frame_mut().rotation = rotation;
+        self
+    }
+
+    /// Rounds off the corners of the element's rect with a radius in logical pixels.
+    /// Ignored for lines, labels and 9-slice skins; a scroll view still cuts its children off at
+    /// the square corners.
+    fn corner_radius(&mut self, radius: f32) -> &mut Self {
+        let frame = self.frame_mut();
+        frame.corner_radius = radius;
+        frame.styling.overrides.corner_radius = Some(radius.into());
+        self
+    }
+}
+
+/// A rect tinted with its color, or drawn with a skin.
 pub struct Panel {
     geometry_type: GeometryType,
-    position: [f32; 3],
-    scale: [f32; 2]
+    frame: Frame,
+    skin: Option<String>,
 }
 
 impl Panel {
     pub(crate) fn new(position: [f32; 3], scale: [f32; 2]) -> Self {
         Self {
             geometry_type: GeometryType::Quadrilateral,
-            position,
-            scale
+            frame: Frame::new(position, scale, WHITE, "panel"),
+            skin: None,
         }
     }
+
+    /// Draws the panel with the UI atlas entry called `sprite`. Entries with 9-slice
+    /// insets keep their corners unscaled, whatever the size of the panel.
+    pub fn skin(&mut self, sprite: &str) -> &mut Self {
+        self.skin = Some(sprite.to_string());
+        self.frame.styling.overrides.skin = self.skin.clone();
+        self
+    }
+}
+
+impl Framed for Panel {
+    fn frame_mut(&mut self) -> &mut Frame {
+        &mut self.frame
+    }
 }
 
 impl Element for Panel {
@@ -21,29 +235,145 @@ impl Element for Panel {
         self.geometry_type
     }
 
-    fn as_instance(&self) -> lib_render::types::Instance {
-        Instance {
-            position: [self.position[0], self.position[1]],
-            scale: self.scale,
-        }
+    fn as_instance(&self) -> Instance {
+        self.frame.instance()
+    }
+
+    fn place(&mut self, position: [f32; 2], scale: [f32; 2]) {
+        self.frame.place(position, scale);
+    }
+
+    fn set_viewport(&mut self, viewport: [f32; 2]) {
+        self.frame.viewport = viewport;
+    }
+
+    fn sprite(&self) -> Option<&str> {
+        self.skin.as_deref()
     }
+
+    fn apply_theme(&mut self, theme: &Theme) {
+        let style = self.frame.apply_theme(theme);
+        self.skin = theme.skin(&style.skin);
+    }
+}
+
+/// The visual states a [`Button`] can be in. When several apply, the first one listed
+/// after `Normal` wins.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ButtonState {
+    Normal,
+    Disabled,
+    Pressed,
+    Hovered,
+    Focused,
+}
+
+/// How a [`Button`] looks in one [`ButtonState`].
+///
+/// Unset fields fall back to the button's skin and to the theme's color for the state
+/// or else a shade of the button's color, so every state gives some feedback without
+/// any styling.
+#[derive(Clone, Debug, Default)]
+pub struct StateStyle {
+    pub color: Option<[f32; 4]>,
+    pub sprite: Option<String>,
+}
+
+/// Mixes the RGB of `color` towards white by `amount`, or towards black for a negative one.
+fn shade(color: [f32; 4], amount: f32) -> [f32; 4] {
+    let target = if amount < 0.0 { 0.0 } else { 1.0 };
+    let amount = amount.abs();
+    [
+        color[0] + (target - color[0]) * amount,
+        color[1] + (target - color[1]) * amount,
+        color[2] + (target - color[2]) * amount,
+        color[3],
+    ]
 }
 
 pub struct Button {
     geometry_type: GeometryType,
-    position: [f32; 3],
-    scale: [f32; 2],
-    action: fn() -> Propogate,
+    frame: Frame,
+    action: Action,
+    skin: Option<String>,
+    disabled: bool,
+    interaction: InteractionState,
+    styles: HashMap<ButtonState, StateStyle>,
+    /// Colors of the states the theme styles.
+    theme_colors: HashMap<ButtonState, [f32; 4]>,
 }
 
 impl Button {
-    pub fn new(position: [f32; 3], scale: [f32; 2], action: fn() -> Propogate) -> Self {
+    pub fn new(position: [f32; 3], scale: [f32; 2], action: impl FnMut(&mut ActionContext) -> Propogate + 'static) -> Self {
         Self {
             geometry_type: GeometryType::Quadrilateral,
-            position,
-            scale,
-            action,
+            frame: Frame::new(position, scale, WHITE, "button"),
+            action: Box::new(action),
+            skin: None,
+            disabled: false,
+            interaction: InteractionState::default(),
+            styles: HashMap::new(),
+            theme_colors: HashMap::new(),
+        }
+    }
+
+    /// Overrides the look of the button in `state`.
+    pub fn style(&mut self, state: ButtonState, style: StateStyle) -> &mut Self {
+        self.styles.insert(state, style);
+        self
+    }
+
+    /// A disabled button ignores the pointer and is drawn in its disabled style.
+    pub fn disabled(&mut self, disabled: bool) -> &mut Self {
+        self.disabled = disabled;
+        self
+    }
+
+    pub fn state(&self) -> ButtonState {
+        if self.disabled {
+            ButtonState::Disabled
+        } else if self.interaction.pressed {
+            ButtonState::Pressed
+        } else if self.interaction.hovered {
+            ButtonState::Hovered
+        } else if self.interaction.focused {
+            ButtonState::Focused
+        } else {
+            ButtonState::Normal
+        }
+    }
+
+    fn current_color(&self) -> [f32; 4] {
+        let state = self.state();
+        if let Some(color) = self.styles.get(&state).and_then(|style| style.color).or_else(|| self.theme_colors.get(&state).copied()) {
+            return color;
         }
+
+        let color = self.frame.color;
+        match state {
+            ButtonState::Normal => color,
+            ButtonState::Hovered => shade(color, 0.15),
+            ButtonState::Focused => shade(color, 0.08),
+            ButtonState::Pressed => shade(color, -0.2),
+            ButtonState::Disabled => {
+                let [r, g, b, a] = color;
+                let luma = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+                [luma, luma, luma, a * 0.5]
+            }
+        }
+    }
+
+    /// Draws the button with the UI atlas entry called `sprite`, see [`Panel::skin`].
+    pub fn skin(&mut self, sprite: &str) -> &mut Self {
+        self.skin = Some(sprite.to_string());
+        self.frame.styling.overrides.skin = self.skin.clone();
+        self
+    }
+}
+
+impl Framed for Button {
+    fn frame_mut(&mut self) -> &mut Frame {
+        &mut self.frame
     }
 }
 
@@ -54,18 +384,285 @@ impl Element for Button {
 
     fn as_instance(&self) -> Instance {
         Instance {
-            position: [self.position[0], self.position[1]],
-            scale: self.scale,
+            color: self.current_color(),
+            ..self.frame.instance()
         }
     }
 
+    fn place(&mut self, position: [f32; 2], scale: [f32; 2]) {
+        self.frame.place(position, scale);
+    }
+
+    fn set_viewport(&mut self, viewport: [f32; 2]) {
+        self.frame.viewport = viewport;
+    }
+
+    fn sprite(&self) -> Option<&str> {
+        self.styles.get(&self.state())
+            .and_then(|style| style.sprite.as_deref())
+            .or(self.skin.as_deref())
+    }
+
+    fn apply_theme(&mut self, theme: &Theme) {
+        let style = self.frame.apply_theme(theme);
+        self.skin = theme.skin(&style.skin);
+        self.theme_colors = [
+            (ButtonState::Hovered, &style.hovered_color),
+            (ButtonState::Pressed, &style.pressed_color),
+            (ButtonState::Focused, &style.focused_color),
+            (ButtonState::Disabled, &style.disabled_color),
+        ]
+        .into_iter()
+        .filter_map(|(state, token)| Some((state, theme.color(token)?)))
+        .collect();
+    }
+
     fn as_clickable(&self) -> Option<&dyn Clickable> {
         Some(self)
     }
+
+    fn as_clickable_mut(&mut self) -> Option<&mut dyn Clickable> {
+        Some(self)
+    }
 }
 
 impl Clickable for Button {
-    fn click(&self) -> Propogate {
-        return (self.action)();
+    fn click(&mut self, context: &mut ActionContext) -> Propogate {
+        (self.action)(context)
     }
-}
\ No newline at end of file
+
+    fn set_interaction(&mut self, state: InteractionState) {
+        self.interaction = state;
+    }
+
+    fn is_disabled(&self) -> bool {
+        self.disabled
+    }
+}
+
+/// A quad drawn with a UI atlas entry, tinted with its color.
+pub struct Icon {
+    geometry_type: GeometryType,
+    frame: Frame,
+    sprite: String,
+}
+
+impl Icon {
+    pub(crate) fn new(position: [f32; 3], scale: [f32; 2], sprite: String) -> Self {
+        Self {
+            geometry_type: GeometryType::Quadrilateral,
+            frame: Frame::new(position, scale, WHITE, "icon"),
+            sprite,
+        }
+    }
+}
+
+impl Framed for Icon {
+    fn frame_mut(&mut self) -> &mut Frame {
+        &mut self.frame
+    }
+}
+
+impl Element for Icon {
+    fn geometry(&self) -> GeometryType {
+        self.geometry_type
+    }
+
+    fn as_instance(&self) -> Instance {
+        self.frame.instance()
+    }
+
+    fn place(&mut self, position: [f32; 2], scale: [f32; 2]) {
+        self.frame.place(position, scale);
+    }
+
+    fn set_viewport(&mut self, viewport: [f32; 2]) {
+        self.frame.viewport = viewport;
+    }
+
+    fn sprite(&self) -> Option<&str> {
+        Some(&self.sprite)
+    }
+
+    fn apply_theme(&mut self, theme: &Theme) {
+        self.frame.apply_theme(theme);
+    }
+}
+
+/// A plain shape drawn with one of the non-rectangular geometry types. Its corner radius
+/// rounds off the corners of its bounding box.
+pub struct Shape {
+    geometry_type: GeometryType,
+    frame: Frame,
+}
+
+impl Shape {
+    pub(crate) fn new(geometry_type: GeometryType, position: [f32; 3], scale: [f32; 2]) -> Self {
+        Self {
+            geometry_type,
+            frame: Frame::new(position, scale, WHITE, "shape"),
+        }
+    }
+}
+
+impl Framed for Shape {
+    fn frame_mut(&mut self) -> &mut Frame {
+        &mut self.frame
+    }
+}
+
+impl Element for Shape {
+    fn geometry(&self) -> GeometryType {
+        self.geometry_type
+    }
+
+    fn as_instance(&self) -> Instance {
+        self.frame.instance()
+    }
+
+    fn place(&mut self, position: [f32; 2], scale: [f32; 2]) {
+        self.frame.place(position, scale);
+    }
+
+    fn set_viewport(&mut self, viewport: [f32; 2]) {
+        self.frame.viewport = viewport;
+    }
+
+    fn apply_theme(&mut self, theme: &Theme) {
+        self.frame.apply_theme(theme);
+    }
+}
+
+/// A straight line segment between two points in logical pixels from the window's top-left corner.
+///
+/// Only the color and depth of its [`Frame`] apply to it: its end points place it.
+pub struct Line {
+    geometry_type: GeometryType,
+    frame: Frame,
+    start: [f32; 2],
+    end: [f32; 2],
+    width: f32,
+}
+
+impl Line {
+    pub(crate) fn new(start: [f32; 2], end: [f32; 2], width: f32) -> Self {
+        Self {
+            geometry_type: GeometryType::Line,
+            frame: Frame::new([0.0; 3], [0.0; 2], WHITE, "line"),
+            start,
+            end,
+            width,
+        }
+    }
+
+    /// Layers the line like the `z` of other elements' positions.
+    pub fn depth(&mut self, depth: f32) -> &mut Self {
+        self.frame.position[2] = depth;
+        self
+    }
+}
+
+impl Framed for Line {
+    fn frame_mut(&mut self) -> &mut Frame {
+        &mut self.frame
+    }
+}
+
+impl Element for Line {
+    fn geometry(&self) -> GeometryType {
+        self.geometry_type
+    }
+
+    fn as_instance(&self) -> Instance {
+        // The renderer turns the offset between the end points into a length and an
+        // angle once it knows the window size.
+        Instance {
+            position: [(self.start[0] + self.end[0]) / 2.0, (self.start[1] + self.end[1]) / 2.0],
+            scale: [self.end[0] - self.start[0], self.end[1] - self.start[1]],
+            color: self.frame.color,
+            depth: self.frame.depth(),
+            ..Default::default()
+        }
+    }
+
+    fn line_width(&self) -> Option<f32> {
+        Some(self.width)
+    }
+
+    fn apply_theme(&mut self, theme: &Theme) {
+        self.frame.apply_theme(theme);
+    }
+}
+
+/// A line of text. Its color is the text's, and it has no size of its own: its pivot is
+/// a point of the text's bounds.
+pub struct Label {
+    frame: Frame,
+    text: String,
+    size: f32,
+    /// The size and color the label was added with, used where the theme doesn't style it.
+    default_size: f32,
+    default_color: [f32; 4],
+}
+
+impl Label {
+    pub(crate) fn new(position: [f32; 3], text: String, size: f32, color: [f32; 4]) -> Self {
+        Self {
+            frame: Frame::new(position, [0.0; 2], color, "label"),
+            text,
+            size,
+            default_size: size,
+            default_color: color,
+        }
+    }
+
+    /// Restyles the label with its text color and font size in `theme`. A color set on
+    /// the label itself wins over the theme's text color.
+    pub(crate) fn apply_theme(&mut self, theme: &Theme) {
+        let style = self.frame.styling.resolve(theme);
+        self.frame.color = theme.color(&self.frame.styling.overrides.color)
+            .or_else(|| theme.color(&style.text_color))
+            .unwrap_or(self.default_color);
+        self.size = theme.font_size(&style.font_size).unwrap_or(self.default_size);
+    }
+
+    pub fn set_text(&mut self, text: &str) -> &mut Self {
+        self.text = text.to_string();
+        self
+    }
+
+    pub(crate) fn set_viewport(&mut self, viewport: [f32; 2]) {
+        self.frame.viewport = viewport;
+    }
+
+    /// Moves the text to the rect with the top-left corner `position` and `size`, in logical pixels.
+    pub(crate) fn place(&mut self, position: [f32; 2], size: [f32; 2]) {
+        let (anchor, pivot) = (self.frame.anchor_point(), self.frame.pivot.point());
+        let [x, y] = [0, 1].map(|axis| position[axis] + pivot[axis] * size[axis] - anchor[axis]);
+        self.frame.position = [x, y, self.frame.position[2]];
+    }
+
+    /// Size of the text in logical pixels.
+    pub(crate) fn measure(&self) -> [f32; 2] {
+        measure_text(&self.text, self.size)
+    }
+
+    pub(crate) fn as_text_section(&self) -> TextSection {
+        let (anchor, position) = (self.frame.anchor_point(), self.frame.position);
+        TextSection {
+            position: [anchor[0] + position[0], anchor[1] + position[1]],
+            pivot: self.frame.pivot.point(),
+            text: self.text.clone(),
+            size: self.size,
+            color: self.frame.color,
+            clip: None,
+            depth: position[2],
+        }
+    }
+}
+
+impl Framed for Label {
+    fn frame_mut(&mut self) -> &mut Frame {
+        &mut self.frame
+    }
+}
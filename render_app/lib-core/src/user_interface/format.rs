@@ -0,0 +1,48 @@
+//! The data formats themes and UI documents can be written in.
+
+use serde::de::DeserializeOwned;
+
+/// The file formats data such as a [`Theme`](super::theme::Theme) or a
+/// [`UiDocument`](super::document::UiDocument) can be written in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DataFormat {
+    Ron,
+    Json,
+    Toml,
+}
+
+impl DataFormat {
+    /// The format of a file, from its extension.
+    pub fn from_file_name(file_name: &str) -> Option<Self> {
+        match file_name.rsplit_once('.')?.1.to_ascii_lowercase().as_str() {
+            "ron" => Some(DataFormat::Ron),
+            "json" => Some(DataFormat::Json),
+            "toml" => Some(DataFormat::Toml),
+            _ => None,
+        }
+    }
+
+    /// Parses `text` written in this format.
+    ///
+    /// RON may leave out the `Some` around optional values and the parentheses around
+    /// the struct in an enum variant, e.g. `Panel(id: "background")`.
+    pub fn parse<T: DeserializeOwned>(self, text: &str) -> anyhow::Result<T> {
+        let value = match self {
+            DataFormat::Ron => ron::Options::default()
+                .with_default_extension(ron::extensions::Extensions::IMPLICIT_SOME | ron::extensions::Extensions::UNWRAP_VARIANT_NEWTYPES)
+                .from_str(text)?,
+            DataFormat::Json => serde_json::from_str(text)?,
+            DataFormat::Toml => toml::from_str(text)?,
+        };
+        Ok(value)
+    }
+}
+
+/// Loads a file from the resources and parses it in the format its extension names.
+pub(crate) async fn load<T: DeserializeOwned>(file_name: &str) -> anyhow::Result<T> {
+    let Some(format) = DataFormat::from_file_name(file_name) else {
+        anyhow::bail!("{:?} isn't a .ron, .json or .toml file", file_name);
+    };
+    let text = lib_render::utilities::resource::load_string(file_name).await?;
+    format.parse(&text)
+}
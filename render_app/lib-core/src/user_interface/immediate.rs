@@ -0,0 +1,249 @@
+use std::{any::{Any, type_name}, collections::hash_map::DefaultHasher, hash::{Hash, Hasher}};
+
+use lib_render::gui_backend::measure_text;
+
+use crate::{types::{GeometryType, Instance, Stage, StagedInstance, TextSection}, user_interface::theme::Theme};
+
+/// Distance in logical pixels between the window edge and the first widget.
+const MARGIN: f32 = 16.0;
+/// Vertical distance in logical pixels between consecutive widgets.
+const SPACING: f32 = 8.0;
+/// Space in logical pixels between a button's text and its edge.
+const BUTTON_PADDING: [f32; 2] = [12.0, 6.0];
+const TEXT_SIZE: f32 = 20.0;
+const TEXT_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+const BUTTON_COLOR: [f32; 4] = [0.25, 0.25, 0.3, 1.0];
+const BUTTON_HOVERED_COLOR: [f32; 4] = [0.35, 0.35, 0.42, 1.0];
+const BUTTON_ACTIVE_COLOR: [f32; 4] = [0.15, 0.15, 0.2, 1.0];
+const BUTTON_CORNER_RADIUS: f32 = 4.0;
+
+/// Identifies a widget across frames, hashed from its label and the enclosing
+/// [`FrameUi::id_scope`]s.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct WidgetId(u64);
+
+/// Pointer input gathered between two frames.
+#[derive(Debug, Default)]
+struct FrameInput {
+    /// Cursor position in logical pixels, `None` while it is outside the window.
+    cursor: Option<[f32; 2]>,
+    down: bool,
+    pressed: bool,
+    released: bool,
+}
+
+/// What survives from one frame to the next.
+#[derive(Debug, Default)]
+struct FrameMemory {
+    /// The widget under the pointer in the last frame.
+    hot: Option<WidgetId>,
+    /// The widget the pointer was pressed on, until it is released.
+    active: Option<WidgetId>,
+}
+
+/// Drives an immediate-mode user interface that is rebuilt every frame by a closure.
+pub(crate) struct ImmediateUi {
+    build: Box<dyn FnMut(&mut FrameUi)>,
+    input: FrameInput,
+    memory: FrameMemory,
+}
+
+impl ImmediateUi {
+    pub(crate) fn new(build: impl FnMut(&mut FrameUi) + 'static) -> Self {
+        Self {
+            build: Box::new(build),
+            input: FrameInput::default(),
+            memory: FrameMemory::default(),
+        }
+    }
+
+    /// Moves the cursor to the point in logical pixels, or out of the window for `None`.
+    pub(crate) fn pointer_moved(&mut self, cursor: Option<[f32; 2]>) {
+        self.input.cursor = cursor;
+    }
+
+    pub(crate) fn pointer_button(&mut self, pressed: bool) {
+        if pressed && !self.input.down {
+            self.input.pressed = true;
+        }
+        if !pressed && self.input.down {
+            self.input.released = true;
+        }
+        self.input.down = pressed;
+    }
+
+    /// Whether the pointer was over a widget in the last frame, or is pressing one. The
+    /// retained interface under the widget doesn't get the pointer's clicks then.
+    pub(crate) fn wants_pointer(&self) -> bool {
+        self.memory.hot.is_some() || self.memory.active.is_some()
+    }
+
+    /// Runs the closure for one frame in a window of `window_size` physical pixels and
+    /// stages what it built, styled with the `"button"` and `"label"` styles of `theme`.
+    pub(crate) fn run_frame(&mut self, window_size: [f32; 2], scale_factor: f32, theme: &Theme, app_state: &mut dyn Any) -> Stage {
+        let mut frame = FrameUi {
+            input: &self.input,
+            memory: &mut self.memory,
+            theme,
+            app_state,
+            window_size: [window_size[0] / scale_factor, window_size[1] / scale_factor],
+            pointer: self.input.cursor,
+            cursor: [MARGIN, MARGIN],
+            id_stack: Vec::new(),
+            hovered: None,
+            stage: Stage::default(),
+        };
+        (self.build)(&mut frame);
+
+        let hovered = frame.hovered;
+        let stage = frame.stage;
+        self.memory.hot = hovered;
+        if self.input.released {
+            self.memory.active = None;
+        }
+        self.input.pressed = false;
+        self.input.released = false;
+        stage
+    }
+}
+
+/// One frame of an immediate-mode user interface. Widgets are laid out top to bottom
+/// and report how they were interacted with as they are added.
+///
+/// Immediate-mode widgets are drawn over the retained interface. Sizes and positions
+/// are in logical pixels.
+pub struct FrameUi<'a> {
+    input: &'a FrameInput,
+    memory: &'a mut FrameMemory,
+    theme: &'a Theme,
+    app_state: &'a mut dyn Any,
+    window_size: [f32; 2],
+    /// Cursor position, `None` while it is outside the window.
+    pointer: Option<[f32; 2]>,
+    /// Top-left corner of the next widget.
+    cursor: [f32; 2],
+    id_stack: Vec<u64>,
+    hovered: Option<WidgetId>,
+    stage: Stage,
+}
+
+impl FrameUi<'_> {
+    /// Size of the window in logical pixels.
+    pub fn window_size(&self) -> [f32; 2] {
+        self.window_size
+    }
+
+    /// The state the application was started with, see [`crate::run_with_state`].
+    ///
+    /// Panics if the state is not a `T`.
+    pub fn state<T: Any>(&mut self) -> &mut T {
+        match self.app_state.downcast_mut() {
+            Some(state) => state,
+            None => panic!("the application state is not a {}", type_name::<T>()),
+        }
+    }
+
+    /// Builds widgets whose IDs are salted with `salt`, so that widgets with the same
+    /// label in different scopes keep separate state.
+    pub fn id_scope<R>(&mut self, salt: impl Hash, build: impl FnOnce(&mut Self) -> R) -> R {
+        let mut hasher = DefaultHasher::new();
+        salt.hash(&mut hasher);
+        self.id_stack.push(hasher.finish());
+        let result = build(self);
+        self.id_stack.pop();
+        result
+    }
+
+    fn widget_id(&self, label: &str) -> WidgetId {
+        let mut hasher = DefaultHasher::new();
+        self.id_stack.hash(&mut hasher);
+        label.hash(&mut hasher);
+        WidgetId(hasher.finish())
+    }
+
+    /// Takes the next `size` logical pixels of the layout, returning their top-left corner.
+    fn allocate(&mut self, size: [f32; 2]) -> [f32; 2] {
+        let position = self.cursor;
+        self.cursor[1] += size[1] + SPACING;
+        position
+    }
+
+    /// Leaves this many logical pixels of empty space before the next widget.
+    pub fn space(&mut self, pixels: f32) {
+        self.cursor[1] += pixels;
+    }
+
+    pub fn label(&mut self, text: &str) {
+        let style = self.theme.style("label");
+        let (text_size, text_color) = (self.theme.font_size(&style.font_size).unwrap_or(TEXT_SIZE), self.theme.color(&style.text_color).unwrap_or(TEXT_COLOR));
+        let size = measure_text(text, text_size);
+        let position = self.allocate(size);
+        self.push_text(position, text, text_size, text_color);
+    }
+
+    /// Adds a button showing `text`. Returns true in the frame after it was clicked,
+    /// i.e. pressed and released with the pointer on it.
+    pub fn button(&mut self, text: &str) -> bool {
+        let id = self.widget_id(text);
+        let style = self.theme.style("button");
+        let font_size = self.theme.font_size(&style.font_size).unwrap_or(TEXT_SIZE);
+        let padding = self.theme.spacing(&style.padding).map_or(BUTTON_PADDING, |padding| [padding; 2]);
+        let text_size = measure_text(text, font_size);
+        let size = [text_size[0] + padding[0] * 2.0, text_size[1] + padding[1] * 2.0];
+        let position = self.allocate(size);
+
+        let hovered = self.pointer.is_some_and(|cursor| {
+            cursor[0] >= position[0] && cursor[0] <= position[0] + size[0] && cursor[1] >= position[1] && cursor[1] <= position[1] + size[1]
+        });
+        if hovered {
+            self.hovered = Some(id);
+            if self.input.pressed {
+                self.memory.active = Some(id);
+            }
+        }
+        let active = self.memory.active == Some(id);
+        let clicked = hovered && active && self.input.released;
+
+        let color = match (active, hovered || self.memory.hot == Some(id)) {
+            (true, _) => self.theme.color(&style.pressed_color).unwrap_or(BUTTON_ACTIVE_COLOR),
+            (false, true) => self.theme.color(&style.hovered_color).unwrap_or(BUTTON_HOVERED_COLOR),
+            (false, false) => self.theme.color(&style.color).unwrap_or(BUTTON_COLOR),
+        };
+        let corner_radius = self.theme.corner_radius(&style.corner_radius).unwrap_or(BUTTON_CORNER_RADIUS);
+        let text_color = self.theme.color(&style.text_color).unwrap_or(TEXT_COLOR);
+        self.push_quad(position, size, color, corner_radius);
+        self.push_text([position[0] + padding[0], position[1] + padding[1]], text, font_size, text_color);
+        clicked
+    }
+
+    fn push_quad(&mut self, position: [f32; 2], size: [f32; 2], color: [f32; 4], corner_radius: f32) {
+        self.stage.elements.push(StagedInstance {
+            id: self.stage.elements.len() as u64,
+            geometry: GeometryType::Quadrilateral,
+            instance: Instance {
+                position: [position[0] + size[0] / 2.0, position[1] + size[1] / 2.0],
+                scale: size,
+                color,
+                corner_radius,
+                depth: f32::MAX,
+                ..Default::default()
+            },
+            sprite: None,
+            line_width: None,
+            overlays: Vec::new(),
+            clip: None,
+        });
+    }
+
+    fn push_text(&mut self, position: [f32; 2], text: &str, size: f32, color: [f32; 4]) {
+        self.stage.text.push(TextSection {
+            position,
+            pivot: [0.0; 2],
+            text: text.to_string(),
+            size,
+            color,
+            clip: None,
+            depth: f32::MAX,
+        });
+    }
+}
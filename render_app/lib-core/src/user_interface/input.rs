@@ -0,0 +1,105 @@
+use std::collections::HashMap;
+
+pub use winit::{event::{Ime, TouchPhase}, keyboard::{Key, KeyCode, ModifiersState, NamedKey}};
+
+use crate::{types::Propogate, user_interface::action::{Action, ActionContext}};
+
+/// A key being pressed or released, as routed to the focused element and the [`KeyMap`].
+#[derive(Clone, Debug)]
+pub struct KeyInput {
+    /// The key's position on the keyboard, whatever the layout.
+    pub code: KeyCode,
+    /// What the key means in the current layout.
+    pub key: Key,
+    /// Text the key produces, e.g. `"A"` for shift and the A key.
+    pub text: Option<String>,
+    pub pressed: bool,
+    /// The press is the operating system repeating a held key.
+    pub repeat: bool,
+    pub modifiers: ModifiersState,
+}
+
+/// A key along with the modifiers held with it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct KeyChord {
+    pub code: KeyCode,
+    pub modifiers: ModifiersState,
+}
+
+impl KeyChord {
+    pub fn new(code: KeyCode) -> Self {
+        Self {
+            code,
+            modifiers: ModifiersState::empty(),
+        }
+    }
+
+    pub fn with(mut self, modifiers: ModifiersState) -> Self {
+        self.modifiers = modifiers;
+        self
+    }
+}
+
+/// What a key chord in the [`KeyMap`] does.
+pub enum Binding {
+    /// Closes the application.
+    Exit,
+    /// Moves the focus to the next element in the tab order.
+    FocusNext,
+    /// Moves the focus to the previous element in the tab order.
+    FocusPrevious,
+    /// Clicks the focused element.
+    Activate,
+    /// Runs an application-level action.
+    Action(Action),
+}
+
+/// Key chords and what they do, consulted for key presses the focused element didn't handle.
+pub struct KeyMap {
+    bindings: HashMap<KeyChord, Binding>,
+}
+
+impl Default for KeyMap {
+    /// Tab and shift-tab move the focus, enter and space activate the focused element
+    /// and escape exits.
+    fn default() -> Self {
+        let mut key_map = Self::empty();
+        key_map
+            .bind(KeyChord::new(KeyCode::Tab), Binding::FocusNext)
+            .bind(KeyChord::new(KeyCode::Tab).with(ModifiersState::SHIFT), Binding::FocusPrevious)
+            .bind(KeyChord::new(KeyCode::Enter), Binding::Activate)
+            .bind(KeyChord::new(KeyCode::NumpadEnter), Binding::Activate)
+            .bind(KeyChord::new(KeyCode::Space), Binding::Activate)
+            .bind(KeyChord::new(KeyCode::Escape), Binding::Exit);
+        key_map
+    }
+}
+
+impl KeyMap {
+    /// A key map without any bindings.
+    pub fn empty() -> Self {
+        Self {
+            bindings: HashMap::new(),
+        }
+    }
+
+    /// Binds `chord`, replacing what it was bound to.
+    pub fn bind(&mut self, chord: KeyChord, binding: Binding) -> &mut Self {
+        self.bindings.insert(chord, binding);
+        self
+    }
+
+    /// Binds `chord` to an application-level action.
+    pub fn bind_action(&mut self, chord: KeyChord, action: impl FnMut(&mut ActionContext) -> Propogate + 'static) -> &mut Self {
+        self.bind(chord, Binding::Action(Box::new(action)))
+    }
+
+    /// Removes the binding of `chord`, returning it.
+    pub fn unbind(&mut self, chord: KeyChord) -> Option<Binding> {
+        self.bindings.remove(&chord)
+    }
+
+    pub(crate) fn get_mut(&mut self, chord: KeyChord) -> Option<&mut Binding> {
+        self.bindings.get_mut(&chord)
+    }
+}
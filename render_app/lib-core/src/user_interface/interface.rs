@@ -1,17 +1,158 @@
-use std::collections::HashMap;
+use std::{any::Any, collections::{HashMap, HashSet}, ops::{Deref, DerefMut}, sync::mpsc::{Receiver, Sender, channel}};
 
-use lib_render::types::{Element, GeometryType, Instance};
+use crate::{types::{Clickable, Element, GeometryType, Instance, InteractionState, Propogate, Stage, StageUpdate, StagedInstance, TextSection}, user_interface::{action::ActionContext, input::{Binding, Ime, KeyChord, KeyInput, KeyMap, TouchPhase}}};
 
-use crate::user_interface::{UserInterface};
+use crate::user_interface::{UserInterface, action::Action, document::UiDocument, elements::Label, immediate::{FrameUi, ImmediateUi}, layout::{Container, Rect}, scroll_view::{ScrollBars, ScrollView}, theme::Theme};
+
+/// Identifies an element or label for as long as it is part of its [`GraphicsInterface`].
+///
+/// IDs are never reused, and a newer element always has a larger ID than an older one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ElementId(pub(crate) u64);
+
+/// A freshly added element, which can be styled further and knows its [`ElementId`].
+pub struct ElementMut<'a, E> {
+    id: ElementId,
+    element: &'a mut E,
+}
+
+impl<E> ElementMut<'_, E> {
+    pub fn id(&self) -> ElementId {
+        self.id
+    }
+}
+
+impl<E> Deref for ElementMut<'_, E> {
+    type Target = E;
+
+    fn deref(&self) -> &E {
+        self.element
+    }
+}
+
+impl<E> DerefMut for ElementMut<'_, E> {
+    fn deref_mut(&mut self) -> &mut E {
+        self.element
+    }
+}
+
+/// A change to the interface queued through [`GraphicsInterface::command_sender`].
+pub type UiCommand = Box<dyn FnOnce(&mut GraphicsInterface) + Send>;
+
+/// Stands in for an element while its action runs.
+struct Detached;
+
+impl Element for Detached {
+    fn geometry(&self) -> GeometryType {
+        GeometryType::Quadrilateral
+    }
+
+    fn as_instance(&self) -> Instance {
+        Instance {
+            scale: [0.0; 2],
+            ..Default::default()
+        }
+    }
+}
+
+/// The overlap of two `[x, y, width, height]` rects.
+fn intersect(a: [f32; 4], b: [f32; 4]) -> [f32; 4] {
+    let [left, top] = [0, 1].map(|axis| a[axis].max(b[axis]));
+    let [right, bottom] = [0, 1].map(|axis| (a[axis] + a[axis + 2]).min(b[axis] + b[axis + 2]));
+    [left, top, (right - left).max(0.0), (bottom - top).max(0.0)]
+}
+
+/// Runs a load of resources in the background.
+#[cfg(target_arch = "wasm32")]
+fn spawn_load(load: impl Future<Output = ()> + 'static) {
+    wasm_bindgen_futures::spawn_local(load);
+}
+
+/// Runs a load of resources in the background.
+#[cfg(not(target_arch = "wasm32"))]
+fn spawn_load(load: impl Future<Output = ()> + Send + 'static) {
+    std::thread::spawn(move || pollster::block_on(load));
+}
+
+/// A finger dragging the scroll views it was put down on.
+struct TouchDrag {
+    finger: u64,
+    /// The scroll views under the finger when it was put down, topmost first.
+    views: Vec<ElementId>,
+    last: [f32; 2],
+}
 
 pub struct GraphicsInterface {
-    elements: HashMap<GeometryType, Vec<Box<dyn Element>>>,
+    /// Elements in the order they were added, which is also the order of their IDs.
+    elements: Vec<(ElementId, Box<dyn Element>)>,
+    labels: Vec<(ElementId, Label)>,
+    next_id: u64,
+    /// The clickable elements the pointer and keyboard are on.
+    hovered: Option<ElementId>,
+    pressed: Option<ElementId>,
+    focused: Option<ElementId>,
+    /// Changes not yet handed to the renderer by [`GraphicsInterface::take_update`].
+    changed: HashSet<ElementId>,
+    removed: Vec<ElementId>,
+    text_changed: bool,
+    commands: (Sender<UiCommand>, Receiver<UiCommand>),
+    immediate: Option<ImmediateUi>,
+    layouts: Vec<Container>,
+    /// Size in physical pixels the layouts were last resolved for.
+    window_size: Option<[f32; 2]>,
+    /// Physical pixels per logical pixel of the window.
+    scale_factor: f32,
+    key_map: KeyMap,
+    /// The order focus moves in with tab, instead of the order elements were added in.
+    tab_order: Option<Vec<ElementId>>,
+    exit_requested: bool,
+    /// The scroll view each element or label scrolls with, if any.
+    parents: HashMap<ElementId, ElementId>,
+    /// Scroll views whose children are being added, innermost last.
+    parent_stack: Vec<ElementId>,
+    /// The element drawing each scroll view's scroll bars.
+    scroll_bars: HashMap<ElementId, ElementId>,
+    touch_drag: Option<TouchDrag>,
+    theme: Theme,
+    /// Elements and labels by the name a [`UiDocument`] gave them.
+    names: HashMap<String, ElementId>,
+    /// Actions by the name documents refer to them with.
+    handlers: HashMap<String, Action>,
+}
+
+impl Default for GraphicsInterface {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl GraphicsInterface {
     pub fn new() -> Self {
         Self {
-            elements: HashMap::new(),
+            elements: Vec::new(),
+            labels: Vec::new(),
+            next_id: 0,
+            hovered: None,
+            pressed: None,
+            focused: None,
+            changed: HashSet::new(),
+            removed: Vec::new(),
+            text_changed: false,
+            commands: channel(),
+            immediate: None,
+            layouts: Vec::new(),
+            window_size: None,
+            scale_factor: 1.0,
+            key_map: KeyMap::default(),
+            tab_order: None,
+            exit_requested: false,
+            parents: HashMap::new(),
+            parent_stack: Vec::new(),
+            scroll_bars: HashMap::new(),
+            touch_drag: None,
+            theme: Theme::default(),
+            names: HashMap::new(),
+            handlers: HashMap::new(),
         }
     }
 
@@ -20,22 +161,1064 @@ impl GraphicsInterface {
         elements_builder(&mut user_interface)
     }
 
-    pub(crate) fn add_element(&mut self, element: impl Element + 'static) {
-        self.elements.entry(element.geometry())
-            .or_insert(Vec::new())
-            .push(Box::new(element));
+    /// Rebuilds an immediate-mode interface with `build` every frame, drawn over the
+    /// elements added with [`GraphicsInterface::show`]. Replaces any earlier one.
+    pub fn on_frame(&mut self, build: impl FnMut(&mut FrameUi) + 'static) {
+        self.immediate = Some(ImmediateUi::new(build));
+    }
+
+    pub(crate) fn immediate_mut(&mut self) -> Option<&mut ImmediateUi> {
+        self.immediate.as_mut()
+    }
+
+    /// Builds the immediate-mode interface for a frame in a window of `window_size`
+    /// physical pixels, if there is one.
+    pub(crate) fn run_immediate(&mut self, window_size: [f32; 2], scale_factor: f32, app_state: &mut dyn Any) -> Option<Stage> {
+        let immediate = self.immediate.as_mut()?;
+        Some(immediate.run_frame(window_size, scale_factor, &self.theme, app_state))
+    }
+
+    fn next_id(&mut self) -> ElementId {
+        self.next_id += 1;
+        ElementId(self.next_id)
+    }
+
+    pub(crate) fn add_element<E: Element>(&mut self, mut element: E) -> ElementMut<'_, E> {
+        if let Some(viewport) = self.viewport() {
+            element.set_viewport(viewport);
+        }
+        element.apply_theme(&self.theme);
+        let id = self.next_id();
+        self.elements.push((id, Box::new(element)));
+        self.changed.insert(id);
+        if let Some(&parent) = self.parent_stack.last() {
+            self.parents.insert(id, parent);
+        }
+
+        let element: &mut dyn Any = self.elements.last_mut().unwrap().1.as_mut();
+        ElementMut {
+            id,
+            element: element.downcast_mut().unwrap(),
+        }
+    }
+
+    pub(crate) fn add_label(&mut self, mut label: Label) -> ElementMut<'_, Label> {
+        if let Some(viewport) = self.viewport() {
+            label.set_viewport(viewport);
+        }
+        label.apply_theme(&self.theme);
+        let id = self.next_id();
+        self.labels.push((id, label));
+        self.text_changed = true;
+        if let Some(&parent) = self.parent_stack.last() {
+            self.parents.insert(id, parent);
+        }
+
+        ElementMut {
+            id,
+            element: &mut self.labels.last_mut().unwrap().1,
+        }
+    }
+
+    /// Adds a scroll view, with the elements and labels that `children` adds scrolling inside it.
+    pub(crate) fn add_scroll_view(&mut self, view: ScrollView, children: impl FnOnce(&mut UserInterface)) -> ElementMut<'_, ScrollView> {
+        let id = self.add_element(view).id();
+        self.parent_stack.push(id);
+        self.show(children);
+        self.parent_stack.pop();
+        // Added after the children, so that it is drawn over those at the same depth.
+        let bars = self.add_element(ScrollBars::new()).id();
+        self.scroll_bars.insert(id, bars);
+
+        let index = self.position(id).expect("the scroll view was removed while adding its children");
+        let element: &mut dyn Any = self.elements[index].1.as_mut();
+        ElementMut {
+            id,
+            element: element.downcast_mut().unwrap(),
+        }
+    }
+
+    fn position(&self, id: ElementId) -> Option<usize> {
+        self.elements.binary_search_by_key(&id, |(id, _)| *id).ok()
+    }
+
+    fn label_position(&self, id: ElementId) -> Option<usize> {
+        self.labels.binary_search_by_key(&id, |(id, _)| *id).ok()
+    }
+
+    pub fn contains(&self, id: ElementId) -> bool {
+        self.position(id).is_some() || self.label_position(id).is_some()
+    }
+
+    /// The element or label with this ID, if it still exists and is an `E`.
+    ///
+    /// The element is restaged on the next update, whether or not it was changed.
+    pub fn get_mut<E: Any>(&mut self, id: ElementId) -> Option<&mut E> {
+        if let Some(index) = self.position(id) {
+            self.changed.insert(id);
+            let element: &mut dyn Any = self.elements[index].1.as_mut();
+            return element.downcast_mut();
+        }
+
+        let index = self.label_position(id)?;
+        self.text_changed = true;
+        let label: &mut dyn Any = &mut self.labels[index].1;
+        label.downcast_mut()
+    }
+
+    /// Removes an element or label, along with the children of a scroll view. Returns
+    /// whether it was there.
+    pub fn remove(&mut self, id: ElementId) -> bool {
+        self.parents.remove(&id);
+        self.names.retain(|_, named| *named != id);
+        if let Some(bars) = self.scroll_bars.remove(&id) {
+            let children: Vec<ElementId> = self.parents.iter().filter(|(_, parent)| **parent == id).map(|(child, _)| *child).collect();
+            for child in children.into_iter().chain([bars]) {
+                self.remove(child);
+            }
+        }
+
+        if let Some(index) = self.position(id) {
+            let (_, element) = self.elements.remove(index);
+            self.text_changed |= element.text().is_some();
+            self.changed.remove(&id);
+            self.removed.push(id);
+            for state in [&mut self.hovered, &mut self.pressed, &mut self.focused] {
+                if *state == Some(id) {
+                    *state = None;
+                }
+            }
+            return true;
+        }
+
+        match self.label_position(id) {
+            Some(index) => {
+                self.labels.remove(index);
+                self.text_changed = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Positions and sizes the items of `layout` now, if the window size is known, and
+    /// again whenever the window is resized.
+    pub fn add_layout(&mut self, layout: Container) {
+        self.layouts.push(layout);
+        if let Some(window_size) = self.window_size {
+            self.apply_layout(self.layouts.len() - 1, window_size);
+        }
+    }
+
+    /// Resolves every layout for a window of `window_size` physical pixels, with
+    /// `scale_factor` physical pixels per logical pixel.
+    pub fn relayout(&mut self, window_size: [f32; 2], scale_factor: f32) {
+        self.window_size = Some(window_size);
+        self.scale_factor = scale_factor;
+        let viewport = [window_size[0] / scale_factor, window_size[1] / scale_factor];
+        for (id, element) in &mut self.elements {
+            element.set_viewport(viewport);
+            self.changed.insert(*id);
+        }
+        for (_, label) in &mut self.labels {
+            label.set_viewport(viewport);
+        }
+        self.text_changed = true;
+        for index in 0..self.layouts.len() {
+            self.apply_layout(index, window_size);
+        }
+    }
+
+    /// Size of the window in logical pixels, once it is known.
+    fn viewport(&self) -> Option<[f32; 2]> {
+        self.window_size.map(|size| [size[0] / self.scale_factor, size[1] / self.scale_factor])
+    }
+
+    fn apply_layout(&mut self, index: usize, window_size: [f32; 2]) {
+        let viewport = [window_size[0] / self.scale_factor, window_size[1] / self.scale_factor];
+        let mut placements = Vec::new();
+        let measure = |id| match self.label_position(id) {
+            Some(index) => self.labels[index].1.measure(),
+            None => [0.0; 2],
+        };
+        self.layouts[index].resolve(viewport, self.scale_factor, &measure, &mut |id, rect| placements.push((id, rect)));
+
+        for (id, rect) in placements {
+            self.place(id, rect);
+        }
+    }
+
+    /// Moves an element or label to `rect`, ignoring items that were removed.
+    fn place(&mut self, id: ElementId, rect: Rect) {
+        if let Some(index) = self.position(id) {
+            let centre = [rect.position[0] + rect.size[0] / 2.0, rect.position[1] + rect.size[1] / 2.0];
+            self.elements[index].1.place(centre, rect.size);
+            self.changed.insert(id);
+        } else if let Some(index) = self.label_position(id) {
+            self.labels[index].1.place(rect.position, rect.size);
+            self.text_changed = true;
+        }
+    }
+
+    pub fn theme(&self) -> &Theme {
+        &self.theme
+    }
+
+    /// Restyles every element and label, and the immediate-mode interface, with `theme`.
+    pub fn set_theme(&mut self, theme: Theme) {
+        theme.warn_missing_tokens();
+        self.theme = theme;
+        self.restyle();
+    }
+
+    /// Applies the theme to every element again.
+    fn restyle(&mut self) {
+        for (id, element) in &mut self.elements {
+            element.apply_theme(&self.theme);
+            self.changed.insert(*id);
+        }
+        for (_, label) in &mut self.labels {
+            label.apply_theme(&self.theme);
+        }
+        self.text_changed = true;
+
+        // Labels may have changed size.
+        if let Some(window_size) = self.window_size {
+            for index in 0..self.layouts.len() {
+                self.apply_layout(index, window_size);
+            }
+        }
+    }
+
+    /// Loads a theme from the resources in the background and switches to it once it has
+    /// loaded, see [`Theme::load`]. If it can't be loaded, the current theme stays.
+    pub fn load_theme(&self, file_name: &str) {
+        let commands = self.command_sender();
+        let file_name = file_name.to_string();
+        spawn_load(async move {
+            match Theme::load(&file_name).await {
+                Ok(theme) => {
+                    // The interface is gone if the application is closing.
+                    let _ = commands.send(Box::new(move |graphics_interface: &mut GraphicsInterface| graphics_interface.set_theme(theme)));
+                }
+                Err(error) => log::error!("Couldn't load the theme {:?}: {}", file_name, error),
+            }
+        });
+    }
+
+    /// Adds the elements of `document`, and its containers as layouts.
+    pub fn add_document(&mut self, document: &UiDocument) {
+        self.show(|ui| document.build(ui));
+        // The elements were themed as they were added, before the document styled them.
+        self.restyle();
+    }
+
+    /// Loads a document from the resources in the background and adds it once it has
+    /// loaded, see [`UiDocument::load`].
+    pub fn load_document(&self, file_name: &str) {
+        let commands = self.command_sender();
+        let file_name = file_name.to_string();
+        spawn_load(async move {
+            match UiDocument::load(&file_name).await {
+                Ok(document) => {
+                    let _ = commands.send(Box::new(move |graphics_interface: &mut GraphicsInterface| graphics_interface.add_document(&document)));
+                }
+                Err(error) => log::error!("Couldn't load the document {:?}: {}", file_name, error),
+            }
+        });
+    }
+
+    /// The element or label a document gave the `id` `name`, if it is still there.
+    pub fn named(&self, name: &str) -> Option<ElementId> {
+        self.names.get(name).copied()
+    }
+
+    pub(crate) fn set_name(&mut self, name: &str, id: ElementId) {
+        if self.names.insert(name.to_string(), id).is_some() {
+            log::warn!("More than one element is called {:?}; the name now refers to the last", name);
+        }
+    }
+
+    /// Registers the action that document elements naming `name` run, replacing any
+    /// registered as `name` before. It can be registered before or after the document loads.
+    pub fn register_handler(&mut self, name: &str, handler: impl FnMut(&mut ActionContext) -> Propogate + 'static) {
+        self.handlers.insert(name.to_string(), Box::new(handler));
+    }
+
+    /// Takes the handler called `name` out while it runs, see [`ActionContext::run_handler`].
+    pub(crate) fn take_handler(&mut self, name: &str) -> Option<Action> {
+        self.handlers.remove(name)
+    }
+
+    /// Puts back a handler after it ran, unless it was replaced while it ran.
+    pub(crate) fn restore_handler(&mut self, name: &str, handler: Action) {
+        self.handlers.entry(name.to_string()).or_insert(handler);
+    }
+
+    /// A sender for changes to make to the interface from outside of element actions,
+    /// e.g. from another thread. They are applied by [`GraphicsInterface::process_commands`].
+    pub fn command_sender(&self) -> Sender<UiCommand> {
+        self.commands.0.clone()
+    }
+
+    /// Applies every change queued through [`GraphicsInterface::command_sender`].
+    pub fn process_commands(&mut self) {
+        while let Ok(command) = self.commands.1.try_recv() {
+            command(self);
+        }
+    }
+
+    /// IDs of the elements in the order they are drawn; the stable sort by depth puts
+    /// the topmost element last.
+    fn draw_order(&self) -> Vec<ElementId> {
+        // Each element's instance is built once rather than on every comparison.
+        let mut order: Vec<(f32, ElementId)> = self.elements.iter().map(|(id, element)| (element.as_instance().depth, *id)).collect();
+        order.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+        order.into_iter().map(|(_, id)| id).collect()
+    }
+
+    fn element(&self, id: ElementId) -> Option<&dyn Element> {
+        self.position(id).map(|index| self.elements[index].1.as_ref())
+    }
+
+    fn scroll_view(&self, id: ElementId) -> Option<&ScrollView> {
+        let element: &dyn Any = self.element(id)?;
+        element.downcast_ref()
+    }
+
+    fn scroll_view_mut(&mut self, id: ElementId) -> Option<&mut ScrollView> {
+        let index = self.position(id)?;
+        let element: &mut dyn Any = self.elements[index].1.as_mut();
+        element.downcast_mut()
+    }
+
+    /// The rect an element or label is cut off at and how far it is scrolled,
+    /// from the scroll views it is in.
+    fn clip_of(&self, id: ElementId) -> (Option<[f32; 4]>, [f32; 2]) {
+        let mut views = Vec::new();
+        let mut current = id;
+        while let Some(&parent) = self.parents.get(&current) {
+            views.push(parent);
+            current = parent;
+        }
+
+        // From the outermost view in, as each view is scrolled by the ones around it.
+        let (mut clip, mut offset) = (None, [0.0; 2]);
+        for view in views.into_iter().rev().filter_map(|view| self.scroll_view(view)) {
+            let [x, y, width, height] = view.rect();
+            let rect = [x - offset[0], y - offset[1], width, height];
+            clip = Some(clip.map_or(rect, |clip| intersect(clip, rect)));
+            offset = [offset[0] + view.offset()[0], offset[1] + view.offset()[1]];
+        }
+        (clip, offset)
+    }
+
+    /// Where `point` on the screen is relative to an element's unscrolled position.
+    fn content_point(&self, id: ElementId, point: [f32; 2]) -> [f32; 2] {
+        let (_, offset) = self.clip_of(id);
+        [point[0] + offset[0], point[1] + offset[1]]
+    }
+
+    /// Whether `point` is on the element as it is drawn, scrolled and cut off by the
+    /// scroll views it is in.
+    fn hit(&self, id: ElementId, element: &dyn Element, point: [f32; 2]) -> bool {
+        let (clip, offset) = self.clip_of(id);
+        let clipped = clip.is_some_and(|[x, y, width, height]| point[0] < x || point[0] > x + width || point[1] < y || point[1] > y + height);
+        !clipped && element.contains([point[0] + offset[0], point[1] + offset[1]])
+    }
+
+    /// The topmost clickable element under `point`.
+    fn clickable_at(&self, point: [f32; 2]) -> Option<ElementId> {
+        self.draw_order().into_iter().rev().find(|&id| {
+            self.element(id).is_some_and(|element| element.as_clickable().is_some() && self.hit(id, element, point))
+        })
+    }
+
+    /// Tells the element with this ID its current interaction state.
+    fn refresh_interaction(&mut self, id: Option<ElementId>) {
+        let Some(id) = id else {
+            return;
+        };
+        let Some(index) = self.position(id) else {
+            return;
+        };
+
+        let state = InteractionState {
+            hovered: self.hovered == Some(id),
+            pressed: self.pressed == Some(id),
+            focused: self.focused == Some(id),
+        };
+        if let Some(clickable) = self.elements[index].1.as_clickable_mut() {
+            clickable.set_interaction(state);
+            self.changed.insert(id);
+        }
+    }
+
+    /// Moves the hover to the element under `point`, in logical pixels, or clears it for
+    /// `None` when the pointer left the window. Returns whether any element changed state.
+    pub fn pointer_moved(&mut self, point: Option<[f32; 2]>) -> bool {
+        let mut changed = false;
+        if let (Some(pressed), Some(point)) = (self.pressed, point)
+            && let Some(index) = self.position(pressed)
+        {
+            let point = self.content_point(pressed, point);
+            if let Some(clickable) = self.elements[index].1.as_clickable_mut() {
+                clickable.pointer_dragged(point);
+                self.changed.insert(pressed);
+                changed = true;
+            }
+        }
+
+        self.update_hover(point) || changed
+    }
+
+    /// Moves the hover to the clickable element under `point`. Returns
+    /// whether it moved.
+    fn update_hover(&mut self, point: Option<[f32; 2]>) -> bool {
+        let hovered = point.and_then(|point| self.clickable_at(point));
+        if hovered == self.hovered {
+            return false;
+        }
+
+        let previous = std::mem::replace(&mut self.hovered, hovered);
+        self.refresh_interaction(previous);
+        self.refresh_interaction(hovered);
+        true
+    }
+
+    /// Presses and focuses the element under `point`, in logical pixels; pressing empty
+    /// space clears the focus. Returns whether any element changed state.
+    pub fn pointer_pressed(&mut self, point: [f32; 2]) -> bool {
+        let target = self.clickable_at(point)
+            .filter(|&id| !self.element(id).and_then(|element| element.as_clickable()).is_some_and(|clickable| clickable.is_disabled()));
+
+        if let Some(id) = target
+            && let Some(index) = self.position(id)
+        {
+            let point = self.content_point(id, point);
+            if let Some(clickable) = self.elements[index].1.as_clickable_mut() {
+                clickable.pointer_pressed(point);
+                self.changed.insert(id);
+            }
+        }
+        if target == self.pressed && target == self.focused {
+            return target.is_some();
+        }
+
+        let previous_pressed = std::mem::replace(&mut self.pressed, target);
+        let previous_focused = std::mem::replace(&mut self.focused, target);
+        self.refresh_interaction(previous_pressed);
+        self.refresh_interaction(previous_focused);
+        self.refresh_interaction(target);
+        true
+    }
+
+    /// Releases the pressed element, clicking it if the pointer is still over it.
+    /// Returns whether any element changed state.
+    pub fn pointer_released(&mut self, point: [f32; 2], app_state: &mut dyn Any) -> bool {
+        let Some(pressed) = self.pressed.take() else {
+            return false;
+        };
+        self.refresh_interaction(Some(pressed));
+
+        if self.clickable_at(point) == Some(pressed) {
+            self.click(point, app_state);
+        }
+        true
+    }
+
+    /// Clicks the clickable elements under `point`, in logical pixels, topmost first, until
+    /// one returns [`Propogate::Ok`]. Returns whether any element handled the click.
+    pub fn click(&mut self, point: [f32; 2], app_state: &mut dyn Any) -> bool {
+        // Decided up front, as the actions may change the interface.
+        let targets: Vec<ElementId> = self.draw_order()
+            .into_iter()
+            .rev()
+            .filter(|&id| self.element(id).is_some_and(|element| element.as_clickable().is_some() && self.hit(id, element, point)))
+            .collect();
+
+        for id in targets {
+            if self.element(id).and_then(|element| element.as_clickable()).is_some_and(|clickable| clickable.is_disabled()) {
+                return false;
+            }
+
+            let propogate = self.with_clickable(id, app_state, |clickable, context| clickable.click(context));
+            if let Some(Propogate::Ok) = propogate {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// The scroll views under `point`, topmost first.
+    fn scroll_views_at(&self, point: [f32; 2]) -> Vec<ElementId> {
+        self.draw_order()
+            .into_iter()
+            .rev()
+            .filter(|&id| self.element(id).is_some_and(|element| (element as &dyn Any).is::<ScrollView>() && self.hit(id, element, point)))
+            .collect()
+    }
+
+    /// Scrolls the first of `views` that can still move by `delta`. Returns whether one did.
+    fn scroll_first(&mut self, views: &[ElementId], delta: [f32; 2]) -> bool {
+        // Fits the views to their children first, which may have changed since the last update.
+        self.refresh_scroll_views();
+        views.iter().any(|&view| self.scroll_view_mut(view).is_some_and(|view| view.scroll_by(delta)))
+    }
+
+    /// Scrolls the topmost scroll view under `point` that can still move by `delta`, so
+    /// that scrolling past the end of a nested view moves the one around it. Both are in
+    /// logical pixels; positive deltas move the content up and to the left. Returns whether a view scrolled.
+    pub fn scroll(&mut self, point: [f32; 2], delta: [f32; 2]) -> bool {
+        let views = self.scroll_views_at(point);
+        if !self.scroll_first(&views, delta) {
+            return false;
+        }
+
+        // Whatever is under the pointer now moved there.
+        self.update_hover(Some(point));
+        true
+    }
+
+    /// Drags the scroll views a finger was put down on along with it, at `point` in
+    /// logical pixels. Returns whether a view scrolled.
+    pub fn touch(&mut self, finger: u64, phase: TouchPhase, point: [f32; 2]) -> bool {
+        match phase {
+            TouchPhase::Started => {
+                let views = self.scroll_views_at(point);
+                self.touch_drag = Some(TouchDrag { finger, views, last: point });
+                false
+            }
+            TouchPhase::Moved => {
+                let Some(mut drag) = self.touch_drag.take_if(|drag| drag.finger == finger) else {
+                    return false;
+                };
+                let scrolled = self.scroll_first(&drag.views, [drag.last[0] - point[0], drag.last[1] - point[1]]);
+                drag.last = point;
+                self.touch_drag = Some(drag);
+                scrolled
+            }
+            TouchPhase::Ended | TouchPhase::Cancelled => {
+                self.touch_drag.take_if(|drag| drag.finger == finger);
+                false
+            }
+        }
+    }
+
+    /// Runs `f` with the clickable element with this ID, taken out of the interface so
+    /// that `f` can borrow the whole interface through its context.
+    fn with_clickable<R>(&mut self, id: ElementId, app_state: &mut dyn Any, f: impl FnOnce(&mut dyn Clickable, &mut ActionContext) -> R) -> Option<R> {
+        let index = self.position(id)?;
+        let mut element = std::mem::replace(&mut self.elements[index].1, Box::new(Detached));
+        let result = element.as_clickable_mut().map(|clickable| f(clickable, &mut ActionContext::for_element(self, app_state, id)));
+        // The action may have removed the element.
+        if let Some(index) = self.position(id) {
+            self.elements[index].1 = element;
+            self.changed.insert(id);
+        }
+        result
+    }
+
+    pub fn focused(&self) -> Option<ElementId> {
+        self.focused
+    }
+
+    /// Whether the element can take the focus: it exists, is clickable and isn't disabled.
+    fn focusable(&self, id: ElementId) -> bool {
+        self.element(id)
+            .and_then(|element| element.as_clickable())
+            .is_some_and(|clickable| !clickable.is_disabled())
+    }
+
+    /// Moves the focus to the element with this ID, or clears it for `None` or an element
+    /// that can't be focused. Returns whether the focus changed.
+    pub fn focus(&mut self, id: Option<ElementId>) -> bool {
+        let id = id.filter(|&id| self.focusable(id));
+        if id == self.focused {
+            return false;
+        }
+
+        let previous = std::mem::replace(&mut self.focused, id);
+        self.refresh_interaction(previous);
+        self.refresh_interaction(id);
+        true
+    }
+
+    /// Sets the order tab moves the focus in. Elements left out can't be reached with tab;
+    /// without an order, focus follows the order elements were added in.
+    pub fn set_tab_order(&mut self, order: Vec<ElementId>) {
+        self.tab_order = Some(order);
+    }
+
+    /// Moves the focus to the next focusable element in the tab order, or the previous one
+    /// if `backwards`, wrapping around at the ends. Returns whether the focus changed.
+    pub fn focus_next(&mut self, backwards: bool) -> bool {
+        let order: Vec<ElementId> = match &self.tab_order {
+            Some(order) => order.iter().copied().filter(|&id| self.focusable(id)).collect(),
+            None => self.elements.iter().map(|(id, _)| *id).filter(|&id| self.focusable(id)).collect(),
+        };
+        if order.is_empty() {
+            return false;
+        }
+
+        let current = self.focused.and_then(|focused| order.iter().position(|&id| id == focused));
+        let next = match (current, backwards) {
+            (None, false) => 0,
+            (None, true) => order.len() - 1,
+            (Some(index), false) => (index + 1) % order.len(),
+            (Some(index), true) => (index + order.len() - 1) % order.len(),
+        };
+        self.focus(Some(order[next]))
+    }
+
+    /// Clicks the focused element. Returns whether it handled the click.
+    pub fn activate(&mut self, app_state: &mut dyn Any) -> bool {
+        let Some(focused) = self.focused.filter(|&id| self.focusable(id)) else {
+            return false;
+        };
+        let propogate = self.with_clickable(focused, app_state, |clickable, context| clickable.click(context));
+        matches!(propogate, Some(Propogate::Ok))
     }
 
-    pub fn stage(&mut self) -> HashMap<GeometryType, Vec<Instance>> {
-        let mut staged_data = HashMap::new();
-        for (geometry, elements) in &self.elements {
-            let mut stage_elements: Vec<Instance> = Vec::new();
-            for element in elements {
-                stage_elements.push(element.as_instance());
-                // TODO: This will ultimately need to make the conversion from elements to instances.
+    pub fn key_map_mut(&mut self) -> &mut KeyMap {
+        &mut self.key_map
+    }
+
+    /// Replaces the key map. The key map can't be changed from an action bound in it.
+    pub fn set_key_map(&mut self, key_map: KeyMap) {
+        self.key_map = key_map;
+    }
+
+    /// Routes a key to the focused element and then, for presses it didn't handle, to the
+    /// key map. Returns whether anything handled the key.
+    pub fn key_input(&mut self, input: &KeyInput, app_state: &mut dyn Any) -> bool {
+        if let Some(focused) = self.focused {
+            let propogate = self.with_clickable(focused, app_state, |clickable, context| clickable.key(input, context));
+            if let Some(Propogate::Ok) = propogate {
+                return true;
             }
-            staged_data.entry(*geometry).insert_entry(stage_elements);
         }
-        return staged_data;
+        if !input.pressed {
+            return false;
+        }
+
+        let chord = KeyChord::new(input.code).with(input.modifiers);
+        let Some(binding) = self.key_map.get_mut(chord) else {
+            return false;
+        };
+        match binding {
+            Binding::Exit => {
+                self.request_exit();
+                true
+            }
+            Binding::FocusNext => self.focus_next(false),
+            Binding::FocusPrevious => self.focus_next(true),
+            Binding::Activate => self.activate(app_state),
+            Binding::Action(_) => {
+                // Take the key map out so the action can borrow the whole interface.
+                let mut key_map = std::mem::replace(&mut self.key_map, KeyMap::empty());
+                let propogate = match key_map.get_mut(chord) {
+                    Some(Binding::Action(action)) => action(&mut ActionContext::new(self, app_state)),
+                    _ => Propogate::Propogate,
+                };
+                self.key_map = key_map;
+                matches!(propogate, Propogate::Ok)
+            }
+        }
+    }
+
+    /// Routes an input method event to the focused element. Returns whether there was one.
+    pub fn ime_input(&mut self, event: &Ime) -> bool {
+        let Some(focused) = self.focused else {
+            return false;
+        };
+        let Some(index) = self.position(focused) else {
+            return false;
+        };
+        let Some(clickable) = self.elements[index].1.as_clickable_mut() else {
+            return false;
+        };
+        clickable.ime(event);
+        self.changed.insert(focused);
+        true
     }
-}
\ No newline at end of file
+
+    /// The centre and size in logical pixels of the focused element, if it takes text.
+    pub fn text_input_area(&self) -> Option<([f32; 2], [f32; 2])> {
+        let element = self.element(self.focused?)?;
+        if !element.as_clickable()?.accepts_text() {
+            return None;
+        }
+        let instance = element.as_instance();
+        let (_, offset) = self.clip_of(self.focused?);
+        Some(([instance.position[0] - offset[0], instance.position[1] - offset[1]], instance.scale))
+    }
+
+    /// Asks the application to close once the current event has been handled.
+    pub fn request_exit(&mut self) {
+        self.exit_requested = true;
+    }
+
+    pub(crate) fn take_exit_request(&mut self) -> bool {
+        std::mem::take(&mut self.exit_requested)
+    }
+
+    /// The IDs of the elements and labels inside a scroll view, including nested ones.
+    fn descendants(&self, view: ElementId) -> Vec<ElementId> {
+        self.parents
+            .keys()
+            .copied()
+            .filter(|&id| {
+                let mut current = id;
+                while let Some(&parent) = self.parents.get(&current) {
+                    if parent == view {
+                        return true;
+                    }
+                    current = parent;
+                }
+                false
+            })
+            .collect()
+    }
+
+    /// Fits every scroll view's content and scroll bars to its children, marking what
+    /// has to be restaged because a view scrolled or moved.
+    fn refresh_scroll_views(&mut self) {
+        let mut views: Vec<(ElementId, ElementId)> = self.scroll_bars.iter().map(|(view, bars)| (*view, *bars)).collect();
+        views.sort();
+
+        for (view, bars) in views {
+            let Some(rect) = self.scroll_view(view).map(ScrollView::rect) else {
+                continue;
+            };
+
+            // How far the children reach from the view's top-left corner, and the depth
+            // that puts the scroll bars over all of them.
+            let mut reach = [0.0f32; 2];
+            let mut depth = self.element(view).map_or(0.0, |element| element.as_instance().depth);
+            for (&child, _) in self.parents.iter().filter(|(_, parent)| **parent == view) {
+                let (min, size) = if let Some(element) = self.element(child) {
+                    let instance = element.as_instance();
+                    depth = depth.max(instance.depth);
+                    let size = instance.scale.map(f32::abs);
+                    ([0, 1].map(|axis| instance.position[axis] - size[axis] / 2.0), size)
+                } else if let Some(index) = self.label_position(child) {
+                    let label = &self.labels[index].1;
+                    let (section, size) = (label.as_text_section(), label.measure());
+                    ([0, 1].map(|axis| section.position[axis] - section.pivot[axis] * size[axis]), size)
+                } else {
+                    continue;
+                };
+                reach = [0, 1].map(|axis| reach[axis].max(min[axis] + size[axis] - rect[axis]));
+            }
+
+            let Some(scroll_view) = self.scroll_view_mut(view) else {
+                continue;
+            };
+            scroll_view.set_content(reach);
+            let moved = scroll_view.take_moved();
+            let thumbs = scroll_view.thumbs(depth);
+
+            if let Some(index) = self.position(bars) {
+                let element: &mut dyn Any = self.elements[index].1.as_mut();
+                if element.downcast_mut::<ScrollBars>().is_some_and(|scroll_bars| scroll_bars.set_thumbs(thumbs, depth)) {
+                    self.changed.insert(bars);
+                }
+            }
+            if moved {
+                for id in self.descendants(view) {
+                    if self.position(id).is_some() {
+                        self.changed.insert(id);
+                    } else {
+                        self.text_changed = true;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Stages an element as it is drawn, scrolled and cut off by the scroll views it is in.
+    fn stage_element(&self, id: ElementId, element: &dyn Element) -> StagedInstance {
+        let (clip, offset) = self.clip_of(id);
+        let scroll = |instance: Instance| Instance {
+            position: [instance.position[0] - offset[0], instance.position[1] - offset[1]],
+            ..instance
+        };
+        StagedInstance {
+            id: id.0,
+            geometry: element.geometry(),
+            instance: scroll(element.as_instance()),
+            sprite: element.sprite().map(str::to_string),
+            line_width: element.line_width(),
+            overlays: element.overlays().into_iter().map(scroll).collect(),
+            clip,
+        }
+    }
+
+    fn stage_text(&self) -> Vec<TextSection> {
+        let scroll = |id: ElementId, section: TextSection| {
+            let (clip, offset) = self.clip_of(id);
+            TextSection {
+                position: [section.position[0] - offset[0], section.position[1] - offset[1]],
+                clip,
+                ..section
+            }
+        };
+        self.labels.iter()
+            .map(|(id, label)| scroll(*id, label.as_text_section()))
+            .chain(self.elements.iter().filter_map(|(id, element)| element.text().map(|section| scroll(*id, section))))
+            .collect()
+    }
+
+    /// Stages the whole interface, discarding any pending changes.
+    pub fn stage(&mut self) -> Stage {
+        for (_, element) in &mut self.elements {
+            element.apply_theme(&self.theme);
+        }
+        for (_, label) in &mut self.labels {
+            label.apply_theme(&self.theme);
+        }
+        self.refresh_scroll_views();
+        self.changed.clear();
+        self.removed.clear();
+        self.text_changed = false;
+
+        Stage {
+            elements: self.elements.iter().map(|(id, element)| self.stage_element(*id, element.as_ref())).collect(),
+            text: self.stage_text(),
+        }
+    }
+
+    /// Stages only what changed since the last call or [`GraphicsInterface::stage`], if anything did.
+    pub fn take_update(&mut self) -> Option<StageUpdate> {
+        self.refresh_scroll_views();
+        if self.changed.is_empty() && self.removed.is_empty() && !self.text_changed {
+            return None;
+        }
+
+        let mut changed: Vec<ElementId> = self.changed.drain().collect();
+        changed.sort();
+        // Styles set on the elements since they were last staged take effect.
+        for &id in &changed {
+            if let Some(index) = self.position(id) {
+                self.elements[index].1.apply_theme(&self.theme);
+            }
+        }
+        if self.text_changed {
+            for (_, label) in &mut self.labels {
+                label.apply_theme(&self.theme);
+            }
+        }
+        self.text_changed |= changed.iter().any(|&id| self.element(id).is_some_and(|element| element.text().is_some()));
+        let elements = changed
+            .into_iter()
+            .filter_map(|id| self.element(id).map(|element| self.stage_element(id, element)))
+            .collect();
+        let text = std::mem::take(&mut self.text_changed).then(|| self.stage_text());
+
+        Some(StageUpdate {
+            elements,
+            removed: self.removed.drain(..).map(|id| id.0).collect(),
+            text,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, rc::Rc};
+
+    use super::*;
+    use crate::user_interface::{elements::Button, input::{Key, KeyCode, ModifiersState, NamedKey}};
+
+    /// Adds a 100 by 50 logical pixel button at `position` that counts its clicks in
+    /// `clicks`, and handles them if `handles`.
+    fn add_button(interface: &mut GraphicsInterface, position: [f32; 3], clicks: &Rc<Cell<u32>>, handles: bool) -> ElementId {
+        let clicks = clicks.clone();
+        interface.show(|ui| {
+            ui.add_button(position, [100.0, 50.0], move |_| {
+                clicks.set(clicks.get() + 1);
+                if handles { Propogate::Ok } else { Propogate::Propogate }
+            })
+            .id()
+        })
+    }
+
+    fn disable(interface: &mut GraphicsInterface, id: ElementId) {
+        interface.get_mut::<Button>(id).unwrap().disabled(true);
+    }
+
+    #[test]
+    fn click_runs_the_topmost_button() {
+        let mut interface = GraphicsInterface::new();
+        let (top, bottom) = (Rc::new(Cell::new(0)), Rc::new(Cell::new(0)));
+        add_button(&mut interface, [0.0, 0.0, 1.0], &top, true);
+        add_button(&mut interface, [0.0, 0.0, 0.0], &bottom, true);
+
+        interface.pointer_pressed([50.0, 25.0]);
+        interface.pointer_released([50.0, 25.0], &mut ());
+        assert_eq!((top.get(), bottom.get()), (1, 0));
+    }
+
+    #[test]
+    fn propogate_passes_the_click_down() {
+        let mut interface = GraphicsInterface::new();
+        let (top, bottom) = (Rc::new(Cell::new(0)), Rc::new(Cell::new(0)));
+        add_button(&mut interface, [0.0, 0.0, 0.0], &bottom, true);
+        add_button(&mut interface, [0.0, 0.0, 0.0], &top, false);
+
+        assert!(interface.click([50.0, 25.0], &mut ()));
+        assert_eq!((top.get(), bottom.get()), (1, 1));
+    }
+
+    #[test]
+    fn release_away_from_the_pressed_button_doesnt_click() {
+        let mut interface = GraphicsInterface::new();
+        let clicks = Rc::new(Cell::new(0));
+        add_button(&mut interface, [0.0, 0.0, 0.0], &clicks, true);
+
+        interface.pointer_pressed([50.0, 25.0]);
+        assert!(interface.pointer_released([150.0, 25.0], &mut ()));
+        assert_eq!(clicks.get(), 0);
+    }
+
+    #[test]
+    fn disabled_button_blocks_clicks_below() {
+        let mut interface = GraphicsInterface::new();
+        let (top, bottom) = (Rc::new(Cell::new(0)), Rc::new(Cell::new(0)));
+        add_button(&mut interface, [0.0, 0.0, 0.0], &bottom, true);
+        let disabled = add_button(&mut interface, [0.0, 0.0, 1.0], &top, false);
+        disable(&mut interface, disabled);
+
+        assert!(!interface.pointer_pressed([50.0, 25.0]));
+        assert!(!interface.pointer_released([50.0, 25.0], &mut ()));
+        assert!(!interface.click([50.0, 25.0], &mut ()));
+        assert_eq!((top.get(), bottom.get()), (0, 0));
+    }
+
+    /// IDs of the elements an update restages.
+    fn restaged(update: &StageUpdate) -> Vec<u64> {
+        update.elements.iter().map(|element| element.id).collect()
+    }
+
+    #[test]
+    fn take_update_is_empty_after_staging() {
+        let mut interface = GraphicsInterface::new();
+        add_button(&mut interface, [0.0, 0.0, 0.0], &Rc::new(Cell::new(0)), true);
+        interface.stage();
+        assert!(interface.take_update().is_none());
+    }
+
+    #[test]
+    fn take_update_restages_only_added_and_changed_elements() {
+        let mut interface = GraphicsInterface::new();
+        let clicks = Rc::new(Cell::new(0));
+        let changed = add_button(&mut interface, [0.0, 0.0, 0.0], &clicks, true);
+        add_button(&mut interface, [200.0, 0.0, 0.0], &clicks, true);
+        interface.stage();
+
+        let added = add_button(&mut interface, [400.0, 0.0, 0.0], &clicks, true);
+        disable(&mut interface, changed);
+        let update = interface.take_update().unwrap();
+        assert_eq!(restaged(&update), [changed.0, added.0]);
+        assert!(update.removed.is_empty());
+        assert!(update.text.is_none());
+        assert!(interface.take_update().is_none());
+    }
+
+    #[test]
+    fn take_update_reports_removed_elements() {
+        let mut interface = GraphicsInterface::new();
+        let clicks = Rc::new(Cell::new(0));
+        let kept = add_button(&mut interface, [0.0, 0.0, 0.0], &clicks, true);
+        let removed = add_button(&mut interface, [200.0, 0.0, 0.0], &clicks, true);
+        interface.stage();
+
+        assert!(interface.remove(removed));
+        let update = interface.take_update().unwrap();
+        assert!(update.elements.is_empty());
+        assert_eq!(update.removed, [removed.0]);
+        assert!(interface.contains(kept));
+    }
+
+    #[test]
+    fn take_update_restages_the_text_when_a_label_changes() {
+        let mut interface = GraphicsInterface::new();
+        let label = interface.show(|ui| ui.add_label([0.0; 3], "before", 18.0, [1.0; 4]).id());
+        interface.stage();
+
+        interface.get_mut::<Label>(label).unwrap().set_text("after");
+        let update = interface.take_update().unwrap();
+        assert!(update.elements.is_empty());
+        let text = update.text.unwrap();
+        assert_eq!(text.iter().map(|section| section.text.as_str()).collect::<Vec<_>>(), ["after"]);
+    }
+
+    fn press(code: KeyCode, key: Key, modifiers: ModifiersState) -> KeyInput {
+        KeyInput {
+            code,
+            key,
+            text: None,
+            pressed: true,
+            repeat: false,
+            modifiers,
+        }
+    }
+
+    fn tab(modifiers: ModifiersState) -> KeyInput {
+        press(KeyCode::Tab, Key::Named(NamedKey::Tab), modifiers)
+    }
+
+    fn escape() -> KeyInput {
+        press(KeyCode::Escape, Key::Named(NamedKey::Escape), ModifiersState::empty())
+    }
+
+    #[test]
+    fn tab_and_shift_tab_move_the_focus_past_disabled_elements() {
+        let mut interface = GraphicsInterface::new();
+        let clicks = Rc::new(Cell::new(0));
+        let first = add_button(&mut interface, [0.0, 0.0, 0.0], &clicks, true);
+        let disabled = add_button(&mut interface, [200.0, 0.0, 0.0], &clicks, true);
+        let last = add_button(&mut interface, [400.0, 0.0, 0.0], &clicks, true);
+        disable(&mut interface, disabled);
+
+        let mut focus_after = |input: KeyInput| {
+            assert!(interface.key_input(&input, &mut ()));
+            interface.focused()
+        };
+        assert_eq!(focus_after(tab(ModifiersState::empty())), Some(first));
+        assert_eq!(focus_after(tab(ModifiersState::empty())), Some(last));
+        assert_eq!(focus_after(tab(ModifiersState::empty())), Some(first));
+        assert_eq!(focus_after(tab(ModifiersState::SHIFT)), Some(last));
+    }
+
+    #[test]
+    fn tab_follows_the_tab_order() {
+        let mut interface = GraphicsInterface::new();
+        let clicks = Rc::new(Cell::new(0));
+        let first = add_button(&mut interface, [0.0, 0.0, 0.0], &clicks, true);
+        add_button(&mut interface, [200.0, 0.0, 0.0], &clicks, true);
+        let last = add_button(&mut interface, [400.0, 0.0, 0.0], &clicks, true);
+        interface.set_tab_order(vec![last, first]);
+
+        interface.key_input(&tab(ModifiersState::empty()), &mut ());
+        assert_eq!(interface.focused(), Some(last));
+        interface.key_input(&tab(ModifiersState::empty()), &mut ());
+        assert_eq!(interface.focused(), Some(first));
+    }
+
+    #[test]
+    fn focused_text_field_takes_escape() {
+        let mut interface = GraphicsInterface::new();
+        let field = interface.show(|ui| ui.add_text_input([0.0; 3], [200.0, 30.0]).id());
+        interface.focus(Some(field));
+
+        // The first escape only drops the focus from the field.
+        assert!(interface.key_input(&escape(), &mut ()));
+        assert_eq!(interface.focused(), None);
+        assert!(!interface.take_exit_request());
+
+        assert!(interface.key_input(&escape(), &mut ()));
+        assert!(interface.take_exit_request());
+    }
+}
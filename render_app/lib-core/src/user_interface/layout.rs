@@ -0,0 +1,588 @@
+use serde::Deserialize;
+
+use crate::user_interface::interface::ElementId;
+
+/// A length along one axis of a layout.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+pub enum Length {
+    /// Logical pixels, which keep the same apparent size on displays of any pixel density.
+    Pixels(f32),
+    /// Pixels of the display, e.g. for hairlines that should stay one pixel wide.
+    PhysicalPixels(f32),
+    /// Percentage of the parent container's content, from 0 to 100.
+    Percent(f32),
+    /// Percentage of the window's width, from 0 to 100.
+    ViewportWidth(f32),
+    /// Percentage of the window's height, from 0 to 100.
+    ViewportHeight(f32),
+    /// The size of the content: the measured text of a label, the children of a
+    /// container, and nothing for other elements.
+    #[default]
+    Auto,
+}
+
+/// Where children sit across a row or column, or within a stack or grid cell.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+pub enum Align {
+    Start,
+    Center,
+    End,
+    /// Fill the available space when the size along that axis is [`Length::Auto`].
+    #[default]
+    Stretch,
+}
+
+/// How the space left over along a row or column is spread between its children.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+pub enum Justify {
+    #[default]
+    Start,
+    Center,
+    End,
+    SpaceBetween,
+}
+
+/// How a container arranges its children.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Arrangement {
+    /// Left to right.
+    Row,
+    /// Top to bottom.
+    Column,
+    /// On top of each other, later children over earlier ones if their depth is equal.
+    Stack,
+    /// Left to right in rows of `columns` equally wide cells. No columns count as one.
+    Grid { columns: usize },
+}
+
+/// Space in logical pixels on each side of a container's content.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Edges {
+    pub left: f32,
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+}
+
+impl Edges {
+    pub fn uniform(size: f32) -> Self {
+        Self::symmetric(size, size)
+    }
+
+    pub fn symmetric(horizontal: f32, vertical: f32) -> Self {
+        Self {
+            left: horizontal,
+            top: vertical,
+            right: horizontal,
+            bottom: vertical,
+        }
+    }
+}
+
+/// A rectangle in logical pixels, from the top-left corner of the window.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Rect {
+    pub position: [f32; 2],
+    pub size: [f32; 2],
+}
+
+/// Sizing shared by items and containers.
+#[derive(Clone, Copy, Debug)]
+struct Sizing {
+    size: [Length; 2],
+    /// Bounds in logical pixels that the resolved size is clamped to.
+    min_size: [f32; 2],
+    max_size: [f32; 2],
+    /// Share of the space left over along the parent row or column.
+    grow: f32,
+}
+
+impl Default for Sizing {
+    fn default() -> Self {
+        Self {
+            size: [Length::Auto; 2],
+            min_size: [0.0; 2],
+            max_size: [f32::INFINITY; 2],
+            grow: 0.0,
+        }
+    }
+}
+
+/// What lengths are resolved with, besides the parent container.
+struct Units<'a> {
+    /// Size of the window in logical pixels.
+    viewport: [f32; 2],
+    /// Physical pixels per logical pixel.
+    scale_factor: f32,
+    /// Size of an item's content in logical pixels.
+    measure: &'a dyn Fn(ElementId) -> [f32; 2],
+}
+
+impl Sizing {
+    /// The size before growing and stretching, for a parent whose content is `parent`
+    /// logical pixels large. `content` is the size of what's inside, used for [`Length::Auto`].
+    fn base_size(&self, parent: [f32; 2], content: [f32; 2], units: &Units) -> [f32; 2] {
+        [0, 1].map(|axis| {
+            let size = match self.size[axis] {
+                Length::Pixels(pixels) => pixels,
+                Length::PhysicalPixels(pixels) => pixels / units.scale_factor,
+                Length::Percent(percent) => parent[axis] * percent / 100.0,
+                Length::ViewportWidth(percent) => units.viewport[0] * percent / 100.0,
+                Length::ViewportHeight(percent) => units.viewport[1] * percent / 100.0,
+                Length::Auto => content[axis],
+            };
+            self.clamp(axis, size)
+        })
+    }
+
+    fn clamp(&self, axis: usize, size: f32) -> f32 {
+        size.min(self.max_size[axis]).max(self.min_size[axis])
+    }
+}
+
+/// An element or label placed by a layout.
+pub struct Item {
+    id: ElementId,
+    sizing: Sizing,
+}
+
+impl Item {
+    pub fn new(id: ElementId) -> Self {
+        Self {
+            id,
+            sizing: Sizing::default(),
+        }
+    }
+
+    pub fn width(mut self, width: Length) -> Self {
+        self.sizing.size[0] = width;
+        self
+    }
+
+    pub fn height(mut self, height: Length) -> Self {
+        self.sizing.size[1] = height;
+        self
+    }
+
+    pub fn min_size(mut self, size: [f32; 2]) -> Self {
+        self.sizing.min_size = size;
+        self
+    }
+
+    pub fn max_size(mut self, size: [f32; 2]) -> Self {
+        self.sizing.max_size = size;
+        self
+    }
+
+    /// Takes this share of the space left over in the parent row or column.
+    pub fn grow(mut self, grow: f32) -> Self {
+        self.sizing.grow = grow;
+        self
+    }
+}
+
+/// Positions and sizes its children, which are items and other containers.
+///
+/// A container added with [`GraphicsInterface::add_layout`](crate::user_interface::interface::GraphicsInterface::add_layout)
+/// is placed at the top-left of the window and fills it, unless given a width or height.
+pub struct Container {
+    arrangement: Arrangement,
+    sizing: Sizing,
+    padding: Edges,
+    gap: f32,
+    align: Align,
+    justify: Justify,
+    children: Vec<Node>,
+}
+
+impl Container {
+    pub fn new(arrangement: Arrangement) -> Self {
+        Self {
+            arrangement,
+            sizing: Sizing::default(),
+            padding: Edges::default(),
+            gap: 0.0,
+            align: Align::default(),
+            justify: Justify::default(),
+            children: Vec::new(),
+        }
+    }
+
+    pub fn row() -> Self {
+        Self::new(Arrangement::Row)
+    }
+
+    pub fn column() -> Self {
+        Self::new(Arrangement::Column)
+    }
+
+    pub fn stack() -> Self {
+        Self::new(Arrangement::Stack)
+    }
+
+    pub fn grid(columns: usize) -> Self {
+        Self::new(Arrangement::Grid { columns: columns.max(1) })
+    }
+
+    pub fn width(mut self, width: Length) -> Self {
+        self.sizing.size[0] = width;
+        self
+    }
+
+    pub fn height(mut self, height: Length) -> Self {
+        self.sizing.size[1] = height;
+        self
+    }
+
+    pub fn min_size(mut self, size: [f32; 2]) -> Self {
+        self.sizing.min_size = size;
+        self
+    }
+
+    pub fn max_size(mut self, size: [f32; 2]) -> Self {
+        self.sizing.max_size = size;
+        self
+    }
+
+    /// Takes this share of the space left over in the parent row or column.
+    pub fn grow(mut self, grow: f32) -> Self {
+        self.sizing.grow = grow;
+        self
+    }
+
+    pub fn padding(mut self, padding: Edges) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    /// Space in logical pixels between consecutive children, and between grid rows.
+    pub fn gap(mut self, gap: f32) -> Self {
+        self.gap = gap;
+        self
+    }
+
+    /// Where children sit across a row or column, or within a stack or grid cell.
+    pub fn align(mut self, align: Align) -> Self {
+        self.align = align;
+        self
+    }
+
+    /// How space left over along a row or column is spread. Ignored by stacks and grids.
+    pub fn justify(mut self, justify: Justify) -> Self {
+        self.justify = justify;
+        self
+    }
+
+    pub fn child(mut self, child: impl Into<Node>) -> Self {
+        self.children.push(child.into());
+        self
+    }
+
+    /// Resolves the container as the root of a layout in a window of `viewport` logical
+    /// pixels, calling `place` with the rect of every item. `measure` gives the content size
+    /// of an item in logical pixels.
+    pub(crate) fn resolve(&self, viewport: [f32; 2], scale_factor: f32, measure: &dyn Fn(ElementId) -> [f32; 2], place: &mut dyn FnMut(ElementId, Rect)) {
+        let units = Units {
+            viewport,
+            scale_factor,
+            measure,
+        };
+        let size = self.sizing.base_size(viewport, viewport, &units);
+        self.arrange(Rect { position: [0.0; 2], size }, &units, place);
+    }
+
+    /// The size of the children along with the padding and gaps between them.
+    fn content_size(&self, units: &Units) -> [f32; 2] {
+        // Percentages of a size that isn't known yet count as nothing.
+        let sizes: Vec<[f32; 2]> = self.children.iter().map(|child| child.base_size([0.0; 2], units)).collect();
+        let gaps = self.gap * sizes.len().saturating_sub(1) as f32;
+        let content = match self.arrangement {
+            Arrangement::Row => [sizes.iter().map(|size| size[0]).sum::<f32>() + gaps, max(sizes.iter().map(|size| size[1]))],
+            Arrangement::Column => [max(sizes.iter().map(|size| size[0])), sizes.iter().map(|size| size[1]).sum::<f32>() + gaps],
+            Arrangement::Stack => [max(sizes.iter().map(|size| size[0])), max(sizes.iter().map(|size| size[1]))],
+            Arrangement::Grid { columns } => {
+                let columns = columns.max(1);
+                let rows: Vec<&[[f32; 2]]> = sizes.chunks(columns).collect();
+                let cell_width = max(sizes.iter().map(|size| size[0]));
+                let width = cell_width * columns.min(sizes.len()) as f32 + self.gap * columns.min(sizes.len()).saturating_sub(1) as f32;
+                let height = rows.iter().map(|row| max(row.iter().map(|size| size[1]))).sum::<f32>() + self.gap * rows.len().saturating_sub(1) as f32;
+                [width, height]
+            }
+        };
+        [
+            content[0] + self.padding.left + self.padding.right,
+            content[1] + self.padding.top + self.padding.bottom,
+        ]
+    }
+
+    /// Places the children inside `rect`, the rect of the container itself.
+    fn arrange(&self, rect: Rect, units: &Units, place: &mut dyn FnMut(ElementId, Rect)) {
+        let content = Rect {
+            position: [rect.position[0] + self.padding.left, rect.position[1] + self.padding.top],
+            size: [
+                (rect.size[0] - self.padding.left - self.padding.right).max(0.0),
+                (rect.size[1] - self.padding.top - self.padding.bottom).max(0.0),
+            ],
+        };
+
+        match self.arrangement {
+            Arrangement::Row => self.arrange_line(content, 0, units, place),
+            Arrangement::Column => self.arrange_line(content, 1, units, place),
+            Arrangement::Stack => {
+                for child in &self.children {
+                    let size = child.base_size(content.size, units);
+                    child.arrange(self.align_in(content, size, child.sizing()), units, place);
+                }
+            }
+            Arrangement::Grid { columns } => {
+                let columns = columns.max(1);
+                let cell_width = ((content.size[0] - self.gap * (columns - 1) as f32) / columns as f32).max(0.0);
+                let cell_parent = [cell_width, content.size[1]];
+                let mut y = content.position[1];
+                for row in self.children.chunks(columns) {
+                    let sizes: Vec<[f32; 2]> = row.iter().map(|child| child.base_size(cell_parent, units)).collect();
+                    let row_height = max(sizes.iter().map(|size| size[1]));
+                    for (column, (child, size)) in row.iter().zip(sizes).enumerate() {
+                        let cell = Rect {
+                            position: [content.position[0] + (cell_width + self.gap) * column as f32, y],
+                            size: [cell_width, row_height],
+                        };
+                        child.arrange(self.align_in(cell, size, child.sizing()), units, place);
+                    }
+                    y += row_height + self.gap;
+                }
+            }
+        }
+    }
+
+    /// Lays the children out one after another along `axis`, 0 for a row and 1 for a column.
+    fn arrange_line(&self, content: Rect, axis: usize, units: &Units, place: &mut dyn FnMut(ElementId, Rect)) {
+        let cross = 1 - axis;
+        let mut sizes: Vec<[f32; 2]> = self.children.iter().map(|child| child.base_size(content.size, units)).collect();
+        let gaps = self.gap * sizes.len().saturating_sub(1) as f32;
+
+        let mut free = content.size[axis] - gaps - sizes.iter().map(|size| size[axis]).sum::<f32>();
+        let total_grow: f32 = self.children.iter().map(|child| child.sizing().grow).sum();
+        if free > 0.0 && total_grow > 0.0 {
+            for (child, size) in self.children.iter().zip(&mut sizes) {
+                let sizing = child.sizing();
+                let grown = size[axis] + free * sizing.grow / total_grow;
+                size[axis] = sizing.clamp(axis, grown);
+            }
+            free = content.size[axis] - gaps - sizes.iter().map(|size| size[axis]).sum::<f32>();
+        }
+        let free = free.max(0.0);
+
+        let (mut offset, spacing) = match self.justify {
+            Justify::Start => (0.0, self.gap),
+            Justify::Center => (free / 2.0, self.gap),
+            Justify::End => (free, self.gap),
+            Justify::SpaceBetween if sizes.len() > 1 => (0.0, self.gap + free / (sizes.len() - 1) as f32),
+            Justify::SpaceBetween => (0.0, self.gap),
+        };
+
+        for (child, mut size) in self.children.iter().zip(sizes) {
+            let sizing = child.sizing();
+            if self.align == Align::Stretch && sizing.size[cross] == Length::Auto {
+                size[cross] = sizing.clamp(cross, content.size[cross]);
+            }
+            let mut position = [0.0; 2];
+            position[axis] = content.position[axis] + offset;
+            position[cross] = content.position[cross] + align_offset(self.align, content.size[cross] - size[cross]);
+            child.arrange(Rect { position, size }, units, place);
+            offset += size[axis] + spacing;
+        }
+    }
+
+    /// Aligns a child of `size` on both axes within `area`, stretching auto sizes.
+    fn align_in(&self, area: Rect, mut size: [f32; 2], sizing: &Sizing) -> Rect {
+        let mut position = area.position;
+        for axis in [0, 1] {
+            if self.align == Align::Stretch && sizing.size[axis] == Length::Auto {
+                size[axis] = sizing.clamp(axis, area.size[axis]);
+            }
+            position[axis] += align_offset(self.align, area.size[axis] - size[axis]);
+        }
+        Rect { position, size }
+    }
+}
+
+fn align_offset(align: Align, free: f32) -> f32 {
+    match align {
+        Align::Start | Align::Stretch => 0.0,
+        Align::Center => free / 2.0,
+        Align::End => free,
+    }
+}
+
+fn max(values: impl Iterator<Item = f32>) -> f32 {
+    values.fold(0.0, f32::max)
+}
+
+/// A child of a [`Container`].
+pub enum Node {
+    Item(Item),
+    Container(Container),
+}
+
+impl Node {
+    fn sizing(&self) -> &Sizing {
+        match self {
+            Node::Item(item) => &item.sizing,
+            Node::Container(container) => &container.sizing,
+        }
+    }
+
+    fn base_size(&self, parent: [f32; 2], units: &Units) -> [f32; 2] {
+        let content = match self {
+            Node::Item(item) => (units.measure)(item.id),
+            Node::Container(container) => container.content_size(units),
+        };
+        self.sizing().base_size(parent, content, units)
+    }
+
+    fn arrange(&self, rect: Rect, units: &Units, place: &mut dyn FnMut(ElementId, Rect)) {
+        match self {
+            Node::Item(item) => place(item.id, rect),
+            Node::Container(container) => container.arrange(rect, units, place),
+        }
+    }
+}
+
+impl From<Item> for Node {
+    fn from(item: Item) -> Self {
+        Node::Item(item)
+    }
+}
+
+impl From<Container> for Node {
+    fn from(container: Container) -> Self {
+        Node::Container(container)
+    }
+}
+
+impl From<ElementId> for Node {
+    fn from(id: ElementId) -> Self {
+        Node::Item(Item::new(id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    const VIEWPORT: [f32; 2] = [300.0, 100.0];
+
+    fn id(id: u64) -> ElementId {
+        ElementId(id)
+    }
+
+    fn item(n: u64, size: [f32; 2]) -> Item {
+        Item::new(id(n)).width(Length::Pixels(size[0])).height(Length::Pixels(size[1]))
+    }
+
+    /// The rect of every item of `container` resolved in the viewport, with items
+    /// measuring 10 by 10 logical pixels.
+    fn resolve(container: &Container) -> HashMap<ElementId, Rect> {
+        let mut rects = HashMap::new();
+        container.resolve(VIEWPORT, 1.0, &|_| [10.0, 10.0], &mut |id, rect| {
+            rects.insert(id, rect);
+        });
+        rects
+    }
+
+    fn rect(position: [f32; 2], size: [f32; 2]) -> Rect {
+        Rect { position, size }
+    }
+
+    #[test]
+    fn grow_shares_the_free_space() {
+        let row = Container::row()
+            .align(Align::Start)
+            .child(item(1, [100.0, 20.0]))
+            .child(item(2, [0.0, 20.0]).grow(1.0))
+            .child(item(3, [0.0, 20.0]).grow(3.0));
+        let rects = resolve(&row);
+        assert_eq!(rects[&id(1)], rect([0.0, 0.0], [100.0, 20.0]));
+        assert_eq!(rects[&id(2)], rect([100.0, 0.0], [50.0, 20.0]));
+        assert_eq!(rects[&id(3)], rect([150.0, 0.0], [150.0, 20.0]));
+    }
+
+    #[test]
+    fn sizes_are_clamped_to_min_and_max() {
+        let row = Container::row()
+            .align(Align::Start)
+            .child(item(1, [10.0, 20.0]).min_size([40.0, 0.0]))
+            .child(item(2, [0.0, 20.0]).grow(1.0).max_size([60.0, f32::INFINITY]))
+            .child(item(3, [500.0, 20.0]).max_size([100.0, 10.0]));
+        let rects = resolve(&row);
+        assert_eq!(rects[&id(1)].size, [40.0, 20.0]);
+        assert_eq!(rects[&id(2)].size, [60.0, 20.0]);
+        assert_eq!(rects[&id(3)], rect([100.0, 0.0], [100.0, 10.0]));
+    }
+
+    #[test]
+    fn auto_sizes_stretch_across_the_line() {
+        let column = Container::column().padding(Edges::uniform(10.0)).gap(5.0).child(id(1)).child(item(2, [50.0, 20.0]));
+        let rects = resolve(&column);
+        assert_eq!(rects[&id(1)], rect([10.0, 10.0], [280.0, 10.0]));
+        assert_eq!(rects[&id(2)], rect([10.0, 25.0], [50.0, 20.0]));
+    }
+
+    #[test]
+    fn justify_spreads_the_free_space() {
+        let row = |justify| Container::row().gap(10.0).justify(justify).align(Align::Start).child(item(1, [50.0, 20.0])).child(item(2, [50.0, 20.0]));
+        let x = |justify| {
+            let rects = resolve(&row(justify));
+            [rects[&id(1)].position[0], rects[&id(2)].position[0]]
+        };
+        assert_eq!(x(Justify::Start), [0.0, 60.0]);
+        assert_eq!(x(Justify::Center), [95.0, 155.0]);
+        assert_eq!(x(Justify::End), [190.0, 250.0]);
+        assert_eq!(x(Justify::SpaceBetween), [0.0, 250.0]);
+    }
+
+    #[test]
+    fn align_places_children_across_the_line() {
+        let y = |align| resolve(&Container::row().align(align).child(item(1, [50.0, 20.0])))[&id(1)].position[1];
+        assert_eq!(y(Align::Start), 0.0);
+        assert_eq!(y(Align::Center), 40.0);
+        assert_eq!(y(Align::End), 80.0);
+        assert_eq!(y(Align::Stretch), 0.0);
+
+        let stack = Container::stack().width(Length::Pixels(100.0)).align(Align::Center).child(item(1, [50.0, 20.0]));
+        assert_eq!(resolve(&stack)[&id(1)], rect([25.0, 40.0], [50.0, 20.0]));
+    }
+
+    #[test]
+    fn grid_fills_rows_of_equal_cells() {
+        let grid = Container::grid(2)
+            .width(Length::Pixels(210.0))
+            .gap(10.0)
+            .align(Align::Start)
+            .child(item(1, [20.0, 20.0]))
+            .child(item(2, [20.0, 30.0]))
+            .child(item(3, [20.0, 40.0]));
+        let rects = resolve(&grid);
+        assert_eq!(rects[&id(1)], rect([0.0, 0.0], [20.0, 20.0]));
+        assert_eq!(rects[&id(2)], rect([110.0, 0.0], [20.0, 30.0]));
+        assert_eq!(rects[&id(3)], rect([0.0, 40.0], [20.0, 40.0]));
+
+        let nested = Container::row().child(Container::grid(2).gap(10.0).child(item(1, [20.0, 20.0])).child(item(2, [30.0, 30.0])).child(item(3, [20.0, 10.0])));
+        assert_eq!(resolve(&nested)[&id(3)].position, [0.0, 40.0]);
+    }
+
+    #[test]
+    fn grid_without_columns_has_one() {
+        let grid = Container::new(Arrangement::Grid { columns: 0 }).align(Align::Start).child(item(1, [20.0, 20.0])).child(item(2, [20.0, 20.0]));
+        let rects = resolve(&grid);
+        assert_eq!(rects[&id(1)].position, [0.0, 0.0]);
+        assert_eq!(rects[&id(2)].position, [0.0, 20.0]);
+
+        let nested = Container::row().child(Container::new(Arrangement::Grid { columns: 0 }).child(id(1)));
+        assert_eq!(resolve(&nested)[&id(1)].size, [10.0, 10.0]);
+    }
+}
@@ -1,22 +1,98 @@
-use lib_render::types::Propogate;
-
-use crate::user_interface::{elements::{Button, Panel}, interface::GraphicsInterface};
+use crate::{types::{GeometryType, Propogate}, user_interface::{action::ActionContext, elements::{Button, Framed, Icon, Label, Line, Panel, Shape}, interface::{ElementId, ElementMut, GraphicsInterface}, layout::Container, scroll_view::ScrollView, text_input::TextInput}};
 
+pub mod action;
+mod clipboard;
 pub mod interface;
+pub mod immediate;
+pub mod input;
+pub mod layout;
+pub mod scroll_view;
+pub mod text_input;
+pub mod theme;
 pub mod elements;
+pub mod format;
+pub mod document;
 
 pub struct UserInterface<'a> {
     graphics_interface: &'a mut GraphicsInterface
 }
 
 impl<'a> UserInterface<'a> {
-    pub fn add_panel(&mut self, position: [f32; 3], scale: [f32; 2]) {
+    /// Adds a panel; give it a skin with [`Panel::skin`].
+    pub fn add_panel(&mut self, position: [f32; 3], scale: [f32; 2]) -> ElementMut<'_, Panel> {
         let element = Panel::new(position, scale);
-        self.graphics_interface.add_element(element);
+        self.graphics_interface.add_element(element)
     }
 
-    pub fn add_button(&mut self, position: [f32; 3], scale: [f32; 2], action: fn() -> Propogate) {
+    /// Adds a button that runs `action` when clicked; give it a skin with [`Button::skin`].
+    pub fn add_button(&mut self, position: [f32; 3], scale: [f32; 2], action: impl FnMut(&mut ActionContext) -> Propogate + 'static) -> ElementMut<'_, Button> {
         let element = Button::new(position, scale, action);
-        self.graphics_interface.add_element(element);
+        self.graphics_interface.add_element(element)
+    }
+
+    /// Adds a quad drawn with the UI atlas entry called `sprite`, e.g. `"close"`.
+    pub fn add_icon(&mut self, position: [f32; 3], scale: [f32; 2], sprite: &str) -> ElementMut<'_, Icon> {
+        let element = Icon::new(position, scale, sprite.to_string());
+        self.graphics_interface.add_element(element)
+    }
+
+    /// Adds an upward-pointing triangle filling the box `scale` at `position`.
+    pub fn add_triangle(&mut self, position: [f32; 3], scale: [f32; 2]) -> ElementMut<'_, Shape> {
+        let element = Shape::new(GeometryType::Triangle, position, scale);
+        self.graphics_interface.add_element(element)
+    }
+
+    /// Adds an ellipse, a circle when `scale` is square.
+    pub fn add_circle(&mut self, position: [f32; 3], scale: [f32; 2]) -> ElementMut<'_, Shape> {
+        let element = Shape::new(GeometryType::Circle, position, scale);
+        self.graphics_interface.add_element(element)
+    }
+
+    /// Adds a rectangle whose corners are rounded off with `corner_radius` logical pixels.
+    pub fn add_rounded_rectangle(&mut self, position: [f32; 3], scale: [f32; 2], corner_radius: f32) -> ElementMut<'_, Shape> {
+        let mut element = Shape::new(GeometryType::RoundedRectangle, position, scale);
+        element.corner_radius(corner_radius);
+        self.graphics_interface.add_element(element)
+    }
+
+    /// Adds a line `width` logical pixels wide between two points in logical pixels.
+    pub fn add_line(&mut self, start: [f32; 2], end: [f32; 2], width: f32) -> ElementMut<'_, Line> {
+        let element = Line::new(start, end, width);
+        self.graphics_interface.add_element(element)
     }
-}
\ No newline at end of file
+
+    /// Adds a line segment between each pair of consecutive `points`, returning their IDs.
+    pub fn add_polyline(&mut self, points: &[[f32; 2]], width: f32, color: [f32; 4]) -> Vec<ElementId> {
+        points
+            .windows(2)
+            .map(|segment| {
+                let mut line = self.add_line(segment[0], segment[1], width);
+                line.color(color);
+                line.id()
+            })
+            .collect()
+    }
+
+    pub fn add_label(&mut self, position: [f32; 3], text: &str, size: f32, color: [f32; 4]) -> ElementMut<'_, Label> {
+        let element = Label::new(position, text.to_string(), size, color);
+        self.graphics_interface.add_label(element)
+    }
+
+    /// Adds an editable text field, see [`TextInput`].
+    pub fn add_text_input(&mut self, position: [f32; 3], scale: [f32; 2]) -> ElementMut<'_, TextInput> {
+        let element = TextInput::new(position, scale);
+        self.graphics_interface.add_element(element)
+    }
+
+    /// Adds a scroll view; the elements and labels that `children` adds scroll inside it
+    /// and are cut off at its edges, see [`ScrollView`].
+    pub fn add_scroll_view(&mut self, position: [f32; 3], scale: [f32; 2], children: impl FnOnce(&mut UserInterface)) -> ElementMut<'_, ScrollView> {
+        let element = ScrollView::new(position, scale);
+        self.graphics_interface.add_scroll_view(element, children)
+    }
+
+    /// Positions and sizes elements and labels with `layout`, see [`GraphicsInterface::add_layout`].
+    pub fn add_layout(&mut self, layout: Container) {
+        self.graphics_interface.add_layout(layout);
+    }
+}
@@ -0,0 +1,218 @@
+use crate::{types::{Element, GeometryType, Instance}, user_interface::{elements::{Frame, Framed}, theme::Theme}};
+
+/// Width in logical pixels of a scroll bar's thumb.
+const BAR_WIDTH: f32 = 6.0;
+/// Space in logical pixels between a scroll bar and the edge of its view.
+const BAR_MARGIN: f32 = 2.0;
+/// Shortest a thumb gets in logical pixels, however long the content.
+const MIN_THUMB_LENGTH: f32 = 24.0;
+const BAR_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 0.5];
+
+/// A rect of the window that its children scroll inside of, cut off at its edges.
+///
+/// Children are positioned as if the view wasn't scrolled; scrolling moves them up and
+/// left by the view's offset. The content is as large as the children reach to the
+/// right of and below the view's top-left corner. The background behind them is
+/// transparent unless given a color.
+pub struct ScrollView {
+    frame: Frame,
+    scroll_bars: bool,
+    bar_color: [f32; 4],
+    /// How far the content is scrolled in logical pixels.
+    offset: [f32; 2],
+    /// Size of the content in logical pixels, at least the size of the view.
+    content: [f32; 2],
+    /// The offset and rect the children were last staged with.
+    staged: Option<([f32; 2], [f32; 4])>,
+}
+
+impl ScrollView {
+    pub(crate) fn new(position: [f32; 3], scale: [f32; 2]) -> Self {
+        Self {
+            frame: Frame::new(position, scale, [0.0; 4], "scroll_view"),
+            scroll_bars: true,
+            bar_color: BAR_COLOR,
+            offset: [0.0; 2],
+            content: scale,
+            staged: None,
+        }
+    }
+
+    /// Whether to show a scroll bar along each axis the content overflows. On by default.
+    pub fn scroll_bars(&mut self, scroll_bars: bool) -> &mut Self {
+        self.scroll_bars = scroll_bars;
+        self
+    }
+
+    /// Colors the scroll bars' thumbs with a linear RGBA color.
+    pub fn bar_color(&mut self, color: [f32; 4]) -> &mut Self {
+        self.bar_color = color;
+        self.frame.styling.overrides.accent_color = Some(color.into());
+        self
+    }
+
+    /// How far the content is scrolled in logical pixels.
+    pub fn offset(&self) -> [f32; 2] {
+        self.offset
+    }
+
+    /// Scrolls to `offset` in logical pixels, limited to the content.
+    pub fn scroll_to(&mut self, offset: [f32; 2]) -> &mut Self {
+        self.offset = offset;
+        self.clamp_offset();
+        self
+    }
+
+    /// The furthest the content can be scrolled along each axis.
+    fn max_offset(&self) -> [f32; 2] {
+        [0, 1].map(|axis| (self.content[axis] - self.frame.scale[axis]).max(0.0))
+    }
+
+    fn clamp_offset(&mut self) {
+        let max_offset = self.max_offset();
+        self.offset = [0, 1].map(|axis| self.offset[axis].clamp(0.0, max_offset[axis]));
+    }
+
+    /// The view's rect as `[x, y, width, height]` in logical pixels.
+    pub(crate) fn rect(&self) -> [f32; 4] {
+        let (centre, scale) = (self.frame.centre(), self.frame.scale);
+        [centre[0] - scale[0] / 2.0, centre[1] - scale[1] / 2.0, scale[0], scale[1]]
+    }
+
+    /// Sets how far the children reach to the right of and below the view's top-left
+    /// corner, limiting the offset to it.
+    pub(crate) fn set_content(&mut self, reach: [f32; 2]) {
+        self.content = [0, 1].map(|axis| reach[axis].max(self.frame.scale[axis]));
+        self.clamp_offset();
+    }
+
+    /// Scrolls by `delta` as far as the content allows. Returns whether the offset changed.
+    pub(crate) fn scroll_by(&mut self, delta: [f32; 2]) -> bool {
+        let previous = self.offset;
+        self.offset = [previous[0] + delta[0], previous[1] + delta[1]];
+        self.clamp_offset();
+        self.offset != previous
+    }
+
+    /// Whether the children have to be restaged because the view scrolled or moved since
+    /// they last were.
+    pub(crate) fn take_moved(&mut self) -> bool {
+        let current = (self.offset, self.rect());
+        self.staged.replace(current) != Some(current)
+    }
+
+    /// The thumbs of the view's scroll bars, drawn at `depth`.
+    pub(crate) fn thumbs(&self, depth: f32) -> Vec<Instance> {
+        if !self.scroll_bars {
+            return Vec::new();
+        }
+
+        let [x, y, width, height] = self.rect();
+        let max_offset = self.max_offset();
+        let mut thumbs = Vec::new();
+
+        // Each thumb is as long, relative to its track, as the view is relative to the content.
+        let thumb = |axis: usize| {
+            let track = self.frame.scale[axis] - BAR_MARGIN * 2.0;
+            let length = (track * self.frame.scale[axis] / self.content[axis]).max(MIN_THUMB_LENGTH).min(track);
+            let start = BAR_MARGIN + (track - length) * self.offset[axis] / max_offset[axis];
+            (start + length / 2.0, length)
+        };
+        if max_offset[1] > 0.0 {
+            let (middle, length) = thumb(1);
+            thumbs.push(Instance {
+                position: [x + width - BAR_MARGIN - BAR_WIDTH / 2.0, y + middle],
+                scale: [BAR_WIDTH, length],
+                color: self.bar_color,
+                corner_radius: BAR_WIDTH / 2.0,
+                depth,
+                ..Default::default()
+            });
+        }
+        if max_offset[0] > 0.0 {
+            let (middle, length) = thumb(0);
+            thumbs.push(Instance {
+                position: [x + middle, y + height - BAR_MARGIN - BAR_WIDTH / 2.0],
+                scale: [length, BAR_WIDTH],
+                color: self.bar_color,
+                corner_radius: BAR_WIDTH / 2.0,
+                depth,
+                ..Default::default()
+            });
+        }
+        thumbs
+    }
+}
+
+impl Framed for ScrollView {
+    fn frame_mut(&mut self) -> &mut Frame {
+        &mut self.frame
+    }
+}
+
+impl Element for ScrollView {
+    fn geometry(&self) -> GeometryType {
+        GeometryType::Quadrilateral
+    }
+
+    fn as_instance(&self) -> Instance {
+        Instance {
+            rotation: 0.0,
+            ..self.frame.instance()
+        }
+    }
+
+    fn set_viewport(&mut self, viewport: [f32; 2]) {
+        self.frame.viewport = viewport;
+    }
+
+    fn apply_theme(&mut self, theme: &Theme) {
+        let style = self.frame.apply_theme(theme);
+        self.bar_color = theme.color(&style.accent_color).unwrap_or(BAR_COLOR);
+    }
+
+    fn place(&mut self, position: [f32; 2], scale: [f32; 2]) {
+        self.frame.place(position, scale);
+    }
+}
+
+/// Draws the scroll bars of a [`ScrollView`] over its children.
+pub(crate) struct ScrollBars {
+    thumbs: Vec<Instance>,
+    depth: f32,
+}
+
+impl ScrollBars {
+    pub(crate) fn new() -> Self {
+        Self {
+            thumbs: Vec::new(),
+            depth: 0.0,
+        }
+    }
+
+    /// Replaces the thumbs, drawn at `depth`. Returns whether anything changed.
+    pub(crate) fn set_thumbs(&mut self, thumbs: Vec<Instance>, depth: f32) -> bool {
+        let same = |a: &Instance, b: &Instance| a.position == b.position && a.scale == b.scale && a.color == b.color;
+        let changed = depth != self.depth || thumbs.len() != self.thumbs.len() || !thumbs.iter().zip(&self.thumbs).all(|(a, b)| same(a, b));
+        (self.thumbs, self.depth) = (thumbs, depth);
+        changed
+    }
+}
+
+impl Element for ScrollBars {
+    fn geometry(&self) -> GeometryType {
+        GeometryType::Quadrilateral
+    }
+
+    fn as_instance(&self) -> Instance {
+        Instance {
+            scale: [0.0; 2],
+            depth: self.depth,
+            ..Default::default()
+        }
+    }
+
+    fn overlays(&self) -> Vec<Instance> {
+        self.thumbs.clone()
+    }
+}
@@ -0,0 +1,567 @@
+use std::ops::Range;
+
+use lib_render::gui_backend::measure_text;
+
+use crate::{types::{Clickable, Element, GeometryType, Instance, InteractionState, Propogate, TextSection}, user_interface::{action::{ActionContext, SubmitAction}, clipboard, elements::{Frame, Framed}, input::{Ime, Key, KeyInput, NamedKey}, theme::Theme}};
+
+/// Space in logical pixels between the text and the left and right edges of the field,
+/// unless the theme pads it.
+const PADDING: f32 = 6.0;
+/// Width of the caret in logical pixels.
+const CARET_WIDTH: f32 = 2.0;
+const COLOR: [f32; 4] = [0.12, 0.12, 0.14, 1.0];
+const CORNER_RADIUS: f32 = 4.0;
+const TEXT_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+const TEXT_SIZE: f32 = 20.0;
+const SELECTION_COLOR: [f32; 4] = [0.25, 0.45, 0.9, 0.5];
+
+/// An editable line of text.
+///
+/// Once focused by a click or tab, typing edits the text. The arrow keys, home and end
+/// move the caret, selecting with shift held and jumping words with ctrl (command on
+/// macOS), and ctrl with A, C, X and V selects everything, copies, cuts and pastes. Escape
+/// clears the selection, or without one gives up the focus. Text being composed with an
+/// input method is shown at the caret until it is committed.
+pub struct TextInput {
+    frame: Frame,
+    padding: f32,
+    text_color: [f32; 4],
+    text_size: f32,
+    selection_color: [f32; 4],
+    value: String,
+    placeholder: String,
+    /// Position of the caret, in characters.
+    caret: usize,
+    /// Where the selection started, in characters. It runs to the caret.
+    selection_anchor: Option<usize>,
+    /// The first character shown, moved to keep the caret in view.
+    scroll: usize,
+    /// Text being composed with the input method, along with the byte offset of its caret.
+    preedit: Option<(String, Option<usize>)>,
+    interaction: InteractionState,
+    on_submit: Option<SubmitAction>,
+}
+
+impl TextInput {
+    pub(crate) fn new(position: [f32; 3], scale: [f32; 2]) -> Self {
+        let mut frame = Frame::new(position, scale, COLOR, "text_input");
+        frame.corner_radius = CORNER_RADIUS;
+        frame.default_corner_radius = CORNER_RADIUS;
+        Self {
+            frame,
+            padding: PADDING,
+            text_color: TEXT_COLOR,
+            text_size: TEXT_SIZE,
+            selection_color: SELECTION_COLOR,
+            value: String::new(),
+            placeholder: String::new(),
+            caret: 0,
+            selection_anchor: None,
+            scroll: 0,
+            preedit: None,
+            interaction: InteractionState::default(),
+            on_submit: None,
+        }
+    }
+
+    /// Colors the text with a linear RGBA color, and the placeholder with it at half the alpha.
+    pub fn text_color(&mut self, color: [f32; 4]) -> &mut Self {
+        self.text_color = color;
+        self.frame.styling.overrides.text_color = Some(color.into());
+        self
+    }
+
+    /// Sets the font height in logical pixels.
+    pub fn text_size(&mut self, size: f32) -> &mut Self {
+        self.text_size = size;
+        self.frame.styling.overrides.font_size = Some(size.into());
+        self.scroll_to_caret();
+        self
+    }
+
+    /// Colors the highlight behind selected text with a linear RGBA color.
+    pub fn selection_color(&mut self, color: [f32; 4]) -> &mut Self {
+        self.selection_color = color;
+        self.frame.styling.overrides.accent_color = Some(color.into());
+        self
+    }
+
+    /// Text shown faded while the field is empty and not focused.
+    pub fn placeholder(&mut self, placeholder: &str) -> &mut Self {
+        self.placeholder = placeholder.to_string();
+        self
+    }
+
+    /// Runs `action` with the field's text when enter is pressed in it.
+    pub fn on_submit(&mut self, action: impl FnMut(&mut ActionContext, &str) -> Propogate + 'static) -> &mut Self {
+        self.on_submit = Some(Box::new(action));
+        self
+    }
+
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// Replaces the text, putting the caret at its end.
+    pub fn set_value(&mut self, value: &str) -> &mut Self {
+        self.value = single_line(value);
+        self.caret = self.char_count();
+        self.selection_anchor = None;
+        self.scroll_to_caret();
+        self
+    }
+
+    /// The selected characters, if any.
+    pub fn selection(&self) -> Option<Range<usize>> {
+        let anchor = self.selection_anchor?;
+        (anchor != self.caret).then(|| anchor.min(self.caret)..anchor.max(self.caret))
+    }
+
+    /// Inserts `text` at the caret, replacing the selection.
+    pub fn insert(&mut self, text: &str) {
+        self.delete_selection();
+        let text = single_line(text);
+        let index = self.byte_index(self.caret);
+        self.value.insert_str(index, &text);
+        self.caret += text.chars().count();
+        self.scroll_to_caret();
+    }
+
+    fn char_count(&self) -> usize {
+        self.value.chars().count()
+    }
+
+    fn byte_index(&self, char_index: usize) -> usize {
+        self.value.char_indices().nth(char_index).map_or(self.value.len(), |(index, _)| index)
+    }
+
+    fn slice(&self, range: Range<usize>) -> &str {
+        &self.value[self.byte_index(range.start)..self.byte_index(range.end)]
+    }
+
+    fn delete(&mut self, range: Range<usize>) {
+        let bytes = self.byte_index(range.start)..self.byte_index(range.end);
+        self.value.replace_range(bytes, "");
+        self.caret = range.start;
+        self.selection_anchor = None;
+    }
+
+    /// Deletes the selected text. Returns whether there was any.
+    fn delete_selection(&mut self) -> bool {
+        match self.selection() {
+            Some(selection) => {
+                self.delete(selection);
+                true
+            }
+            None => {
+                self.selection_anchor = None;
+                false
+            }
+        }
+    }
+
+    fn move_caret(&mut self, to: usize, select: bool) {
+        match select {
+            true => {
+                self.selection_anchor.get_or_insert(self.caret);
+            }
+            false => self.selection_anchor = None,
+        }
+        self.caret = to;
+    }
+
+    /// The start of the word before `from`.
+    fn word_start(&self, from: usize) -> usize {
+        let characters: Vec<char> = self.value.chars().take(from).collect();
+        let spaces = characters.iter().rev().take_while(|character| character.is_whitespace()).count();
+        let word = characters.iter().rev().skip(spaces).take_while(|character| !character.is_whitespace()).count();
+        from - spaces - word
+    }
+
+    /// The end of the word after `from`.
+    fn word_end(&self, from: usize) -> usize {
+        let mut characters = self.value.chars().skip(from).peekable();
+        let mut end = from;
+        while characters.next_if(|character| character.is_whitespace()).is_some() {
+            end += 1;
+        }
+        while characters.next_if(|character| !character.is_whitespace()).is_some() {
+            end += 1;
+        }
+        end
+    }
+
+    fn copy(&self) {
+        if let Some(selection) = self.selection() {
+            clipboard::write(self.slice(selection));
+        }
+    }
+
+    fn paste(&self, context: &mut ActionContext) {
+        let Some(id) = context.element() else {
+            return;
+        };
+        clipboard::read(context.graphics_interface.command_sender(), move |graphics_interface, text| {
+            if let Some(input) = graphics_interface.get_mut::<TextInput>(id) {
+                input.insert(&text);
+            }
+        });
+    }
+
+    /// Width in logical pixels of the characters in `range`.
+    fn width(&self, range: Range<usize>) -> f32 {
+        measure_text(self.slice(range), self.text_size)[0]
+    }
+
+    /// Top-left corner and size of the field in logical pixels.
+    fn bounds(&self) -> ([f32; 2], [f32; 2]) {
+        let (centre, size) = (self.frame.centre(), self.frame.scale);
+        ([centre[0] - size[0] / 2.0, centre[1] - size[1] / 2.0], size)
+    }
+
+    fn text_width(&self) -> f32 {
+        (self.bounds().1[0] - self.padding * 2.0).max(0.0)
+    }
+
+    /// The end of the characters that fit in the field from the scroll position on.
+    fn visible_end(&self) -> usize {
+        let width = self.text_width();
+        let mut end = self.scroll;
+        while end < self.char_count() && self.width(self.scroll..end + 1) <= width {
+            end += 1;
+        }
+        end
+    }
+
+    /// Scrolls the text so that the caret is in view, showing as much text as fits.
+    fn scroll_to_caret(&mut self) {
+        let width = self.text_width();
+        self.scroll = self.scroll.min(self.caret);
+        while self.scroll < self.caret && self.width(self.scroll..self.caret) > width {
+            self.scroll += 1;
+        }
+        while self.scroll > 0 && self.width(self.scroll - 1..self.char_count()) <= width {
+            self.scroll -= 1;
+        }
+    }
+
+    /// The caret position closest to `point`, in logical pixels.
+    fn caret_at(&self, point: [f32; 2]) -> usize {
+        let x = point[0] - self.bounds().0[0] - self.padding;
+        let end = self.visible_end();
+        (self.scroll..end)
+            .find(|&index| x < (self.width(self.scroll..index) + self.width(self.scroll..index + 1)) / 2.0)
+            .unwrap_or(end)
+    }
+
+    /// A solid quad from `x` to `x + width` logical pixels, vertically centred in the field.
+    fn overlay(&self, x: f32, width: f32, height: f32, color: [f32; 4]) -> Instance {
+        let (position, size) = self.bounds();
+        let centre = [x + width / 2.0, position[1] + size[1] / 2.0];
+        Instance {
+            position: centre,
+            scale: [width, height],
+            color,
+            depth: self.frame.depth(),
+            ..Default::default()
+        }
+    }
+}
+
+/// `text` without line breaks and other control characters.
+fn single_line(text: &str) -> String {
+    text.chars().filter(|character| !character.is_control()).collect()
+}
+
+impl Framed for TextInput {
+    fn frame_mut(&mut self) -> &mut Frame {
+        &mut self.frame
+    }
+}
+
+impl Element for TextInput {
+    fn geometry(&self) -> GeometryType {
+        GeometryType::Quadrilateral
+    }
+
+    fn as_instance(&self) -> Instance {
+        Instance {
+            rotation: 0.0,
+            ..self.frame.instance()
+        }
+    }
+
+    fn place(&mut self, position: [f32; 2], scale: [f32; 2]) {
+        self.frame.place(position, scale);
+        self.scroll_to_caret();
+    }
+
+    fn set_viewport(&mut self, viewport: [f32; 2]) {
+        self.frame.viewport = viewport;
+    }
+
+    fn apply_theme(&mut self, theme: &Theme) {
+        let style = self.frame.apply_theme(theme);
+        self.padding = theme.spacing(&style.padding).unwrap_or(PADDING);
+        self.text_color = theme.color(&style.text_color).unwrap_or(TEXT_COLOR);
+        self.selection_color = theme.color(&style.accent_color).unwrap_or(SELECTION_COLOR);
+        self.text_size = theme.font_size(&style.font_size).unwrap_or(TEXT_SIZE);
+        self.scroll_to_caret();
+    }
+
+    fn overlays(&self) -> Vec<Instance> {
+        let left = self.bounds().0[0] + self.padding;
+        let line_height = measure_text("", self.text_size)[1];
+        let end = self.visible_end();
+        let mut overlays = Vec::new();
+
+        if let Some(selection) = self.selection() {
+            let (start, end) = (selection.start.clamp(self.scroll, end), selection.end.clamp(self.scroll, end));
+            let x = left + self.width(self.scroll..start);
+            overlays.push(self.overlay(x, self.width(self.scroll..end) - self.width(self.scroll..start), line_height, self.selection_color));
+        }
+
+        if self.interaction.focused {
+            let mut x = left + self.width(self.scroll..self.caret);
+            if let Some((preedit, cursor)) = &self.preedit {
+                // Underline the composed text, which is drawn at the caret.
+                let preedit_width = measure_text(preedit, self.text_size)[0];
+                let underline = self.overlay(x, preedit_width, 1.0, self.text_color);
+                overlays.push(Instance {
+                    position: [underline.position[0], underline.position[1] + line_height / 2.0],
+                    ..underline
+                });
+                x += measure_text(&preedit[..cursor.unwrap_or(preedit.len())], self.text_size)[0];
+            }
+            overlays.push(self.overlay(x, CARET_WIDTH, line_height, self.text_color));
+        }
+        overlays
+    }
+
+    fn text(&self) -> Option<TextSection> {
+        let (text, color) = match (&self.preedit, self.value.is_empty() && !self.interaction.focused) {
+            (_, true) => {
+                let [r, g, b, a] = self.text_color;
+                (self.placeholder.clone(), [r, g, b, a * 0.5])
+            }
+            (Some((preedit, _)), false) => (
+                [self.slice(self.scroll..self.caret), preedit, self.slice(self.caret..self.visible_end())].concat(),
+                self.text_color,
+            ),
+            (None, false) => (self.slice(self.scroll..self.visible_end()).to_string(), self.text_color),
+        };
+
+        let (position, size) = self.bounds();
+        Some(TextSection {
+            position: [position[0] + self.padding, position[1] + size[1] / 2.0],
+            pivot: [0.0, 0.5],
+            text,
+            size: self.text_size,
+            color,
+            clip: None,
+            depth: self.frame.depth(),
+        })
+    }
+
+    fn as_clickable(&self) -> Option<&dyn Clickable> {
+        Some(self)
+    }
+
+    fn as_clickable_mut(&mut self) -> Option<&mut dyn Clickable> {
+        Some(self)
+    }
+}
+
+impl Clickable for TextInput {
+    fn click(&mut self, _context: &mut ActionContext) -> Propogate {
+        Propogate::Ok
+    }
+
+    fn set_interaction(&mut self, state: InteractionState) {
+        if !state.focused {
+            self.preedit = None;
+        }
+        self.interaction = state;
+    }
+
+    fn key(&mut self, input: &KeyInput, context: &mut ActionContext) -> Propogate {
+        if !input.pressed {
+            return Propogate::Propogate;
+        }
+
+        let shortcut = input.modifiers.control_key() || input.modifiers.super_key();
+        let select = input.modifiers.shift_key();
+        match &input.key {
+            Key::Named(NamedKey::ArrowLeft) => {
+                let to = match (shortcut, self.selection()) {
+                    (true, _) => self.word_start(self.caret),
+                    (false, Some(selection)) if !select => selection.start,
+                    (false, _) => self.caret.saturating_sub(1),
+                };
+                self.move_caret(to, select);
+            }
+            Key::Named(NamedKey::ArrowRight) => {
+                let to = match (shortcut, self.selection()) {
+                    (true, _) => self.word_end(self.caret),
+                    (false, Some(selection)) if !select => selection.end,
+                    (false, _) => (self.caret + 1).min(self.char_count()),
+                };
+                self.move_caret(to, select);
+            }
+            Key::Named(NamedKey::Home) => self.move_caret(0, select),
+            Key::Named(NamedKey::End) => self.move_caret(self.char_count(), select),
+            Key::Named(NamedKey::Backspace) => {
+                if !self.delete_selection() {
+                    let start = if shortcut { self.word_start(self.caret) } else { self.caret.saturating_sub(1) };
+                    self.delete(start..self.caret);
+                }
+            }
+            Key::Named(NamedKey::Delete) => {
+                if !self.delete_selection() {
+                    let end = if shortcut { self.word_end(self.caret) } else { (self.caret + 1).min(self.char_count()) };
+                    self.delete(self.caret..end);
+                }
+            }
+            Key::Named(NamedKey::Enter) => {
+                return match &mut self.on_submit {
+                    Some(action) => action(context, &self.value),
+                    None => Propogate::Propogate,
+                };
+            }
+            // Escape drops the selection, then the focus, rather than reaching the key map.
+            Key::Named(NamedKey::Escape) => {
+                if self.selection().is_none() {
+                    context.graphics_interface.focus(None);
+                    // The field is out of the interface, so the focus change can't reach it.
+                    self.set_interaction(InteractionState { focused: false, ..self.interaction });
+                }
+                self.selection_anchor = None;
+            }
+            Key::Named(NamedKey::Tab) => return Propogate::Propogate,
+            Key::Character(character) if shortcut => match character.to_lowercase().as_str() {
+                "a" => {
+                    self.selection_anchor = Some(0);
+                    self.caret = self.char_count();
+                }
+                "c" => self.copy(),
+                "x" => {
+                    self.copy();
+                    self.delete_selection();
+                }
+                "v" => self.paste(context),
+                _ => return Propogate::Propogate,
+            },
+            _ => match &input.text {
+                Some(text) if !shortcut && !single_line(text).is_empty() => self.insert(text),
+                _ => return Propogate::Propogate,
+            },
+        }
+
+        self.scroll_to_caret();
+        Propogate::Ok
+    }
+
+    fn pointer_pressed(&mut self, point: [f32; 2]) {
+        let caret = self.caret_at(point);
+        self.caret = caret;
+        self.selection_anchor = Some(caret);
+    }
+
+    fn pointer_dragged(&mut self, point: [f32; 2]) {
+        self.caret = self.caret_at(point);
+        self.scroll_to_caret();
+    }
+
+    fn accepts_text(&self) -> bool {
+        true
+    }
+
+    fn ime(&mut self, event: &Ime) {
+        match event {
+            Ime::Preedit(text, cursor) if !text.is_empty() => self.preedit = Some((text.clone(), cursor.map(|(start, _)| start))),
+            Ime::Preedit(..) | Ime::Enabled | Ime::Disabled => self.preedit = None,
+            Ime::Commit(text) => {
+                self.preedit = None;
+                self.insert(text);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A field holding `value`, with the caret at `caret`.
+    fn field(value: &str, caret: usize) -> TextInput {
+        let mut input = TextInput::new([0.0; 3], [200.0, 30.0]);
+        input.set_value(value);
+        input.move_caret(caret, false);
+        input
+    }
+
+    #[test]
+    fn insert_counts_characters_not_bytes() {
+        let mut input = field("héllo", 2);
+        input.insert("ü€");
+        assert_eq!(input.value(), "héü€llo");
+        assert_eq!(input.caret, 4);
+    }
+
+    #[test]
+    fn insert_replaces_the_selection() {
+        let mut input = field("日本語です", 1);
+        input.move_caret(3, true);
+        assert_eq!(input.selection(), Some(1..3));
+        input.insert("x");
+        assert_eq!(input.value(), "日xです");
+        assert_eq!((input.caret, input.selection()), (2, None));
+    }
+
+    #[test]
+    fn insert_drops_line_breaks() {
+        let mut input = field("", 0);
+        input.insert("a\nb\tc");
+        assert_eq!(input.value(), "abc");
+    }
+
+    #[test]
+    fn delete_removes_a_character_range() {
+        let mut input = field("añb€c", 5);
+        input.delete(1..3);
+        assert_eq!(input.value(), "a€c");
+        assert_eq!(input.caret, 1);
+    }
+
+    #[test]
+    fn word_start_skips_spaces_then_a_word() {
+        let input = field("naïve café  über", 16);
+        assert_eq!(input.word_start(16), 12);
+        assert_eq!(input.word_start(12), 6);
+        assert_eq!(input.word_start(8), 6);
+        assert_eq!(input.word_start(5), 0);
+        assert_eq!(input.word_start(0), 0);
+    }
+
+    #[test]
+    fn word_end_skips_spaces_then_a_word() {
+        let input = field("naïve café  über", 0);
+        assert_eq!(input.word_end(0), 5);
+        assert_eq!(input.word_end(5), 10);
+        assert_eq!(input.word_end(10), 16);
+        assert_eq!(input.word_end(16), 16);
+    }
+
+    #[test]
+    fn selection_runs_from_the_anchor_to_the_caret() {
+        let mut input = field("€uro", 4);
+        input.move_caret(1, true);
+        assert_eq!(input.selection(), Some(1..4));
+        assert_eq!(input.slice(1..4), "uro");
+
+        assert!(input.delete_selection());
+        assert_eq!(input.value(), "€");
+        assert_eq!((input.caret, input.selection()), (1, None));
+        assert!(!input.delete_selection());
+    }
+}
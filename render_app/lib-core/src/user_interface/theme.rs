@@ -0,0 +1,193 @@
+//! Colors, sizes and skins for the user interface, loaded from data.
+//!
+//! A [`Theme`] holds named values and a [`Style`] per class of element, e.g. `"button"`.
+//! Each element is styled by its class's style with its own override on top, so that
+//! swapping the theme restyles everything that wasn't styled by hand.
+
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+use crate::user_interface::format::{self, DataFormat};
+
+/// A value in a [`Style`], either given as is or as the name of one of the theme's values.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum Token<T> {
+    Named(String),
+    Value(T),
+}
+
+impl<T> From<T> for Token<T> {
+    fn from(value: T) -> Self {
+        Token::Value(value)
+    }
+}
+
+/// How one class of element looks. Unset fields leave the element as it is.
+///
+/// Not every element uses every field: panels, buttons and scroll views use the color,
+/// corner radius and skin, labels and text fields the text color and font size, text
+/// fields and immediate-mode buttons pad their text with the padding, and text fields
+/// and scroll views draw their selection and scroll bars with the accent color.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Style {
+    pub color: Option<Token<[f32; 4]>>,
+    /// Colors of a button while hovered, pressed, focused and disabled, instead of shades of its color.
+    pub hovered_color: Option<Token<[f32; 4]>>,
+    pub pressed_color: Option<Token<[f32; 4]>>,
+    pub focused_color: Option<Token<[f32; 4]>>,
+    pub disabled_color: Option<Token<[f32; 4]>>,
+    pub text_color: Option<Token<[f32; 4]>>,
+    pub accent_color: Option<Token<[f32; 4]>>,
+    /// Font height in logical pixels, or the name of one of the theme's font sizes.
+    pub font_size: Option<Token<f32>>,
+    /// Radius in logical pixels, or the name of one of the theme's corner radii.
+    pub corner_radius: Option<Token<f32>>,
+    /// Space in logical pixels around content, or the name of one of the theme's spacings.
+    pub padding: Option<Token<f32>>,
+    /// The name of one of the theme's skins, or else of a UI atlas entry.
+    pub skin: Option<String>,
+}
+
+impl Style {
+    /// This style with the fields set in `over` replaced.
+    pub fn merged(&self, over: &Style) -> Style {
+        Style {
+            color: over.color.clone().or_else(|| self.color.clone()),
+            hovered_color: over.hovered_color.clone().or_else(|| self.hovered_color.clone()),
+            pressed_color: over.pressed_color.clone().or_else(|| self.pressed_color.clone()),
+            focused_color: over.focused_color.clone().or_else(|| self.focused_color.clone()),
+            disabled_color: over.disabled_color.clone().or_else(|| self.disabled_color.clone()),
+            text_color: over.text_color.clone().or_else(|| self.text_color.clone()),
+            accent_color: over.accent_color.clone().or_else(|| self.accent_color.clone()),
+            font_size: over.font_size.clone().or_else(|| self.font_size.clone()),
+            corner_radius: over.corner_radius.clone().or_else(|| self.corner_radius.clone()),
+            padding: over.padding.clone().or_else(|| self.padding.clone()),
+            skin: over.skin.clone().or_else(|| self.skin.clone()),
+        }
+    }
+}
+
+/// The theme class an element is styled with and the style it overrides it with.
+#[derive(Clone, Debug)]
+pub(crate) struct Styling {
+    class: String,
+    pub(crate) overrides: Style,
+}
+
+impl Styling {
+    pub(crate) fn new(class: &str) -> Self {
+        Self {
+            class: class.to_string(),
+            overrides: Style::default(),
+        }
+    }
+
+    pub(crate) fn set_class(&mut self, class: &str) {
+        self.class = class.to_string();
+    }
+
+    /// The element's style in `theme`.
+    pub(crate) fn resolve(&self, theme: &Theme) -> Style {
+        theme.style(&self.class).merged(&self.overrides)
+    }
+}
+
+/// Named colors, font sizes, spacings, corner radii and skins, and the [`Style`] of each
+/// class of element. The default theme is empty and leaves every element as it is built.
+///
+/// In RON, a theme looks like:
+///
+/// ```ron
+/// (
+///     colors: { "surface": (0.1, 0.1, 0.12, 1.0), "accent": (0.3, 0.5, 0.9, 1.0) },
+///     corner_radii: { "small": 4.0 },
+///     styles: {
+///         "button": (color: "accent", corner_radius: "small"),
+///         "label": (text_color: (1.0, 1.0, 1.0, 1.0), font_size: 18.0),
+///     },
+/// )
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Theme {
+    pub colors: HashMap<String, [f32; 4]>,
+    pub font_sizes: HashMap<String, f32>,
+    pub spacing: HashMap<String, f32>,
+    pub corner_radii: HashMap<String, f32>,
+    /// UI atlas entries by the name styles refer to them with.
+    pub skins: HashMap<String, String>,
+    pub styles: HashMap<String, Style>,
+}
+
+/// Looks up a token's value in `values`, `None` for names the theme doesn't have.
+fn lookup<T: Copy>(token: &Option<Token<T>>, values: &HashMap<String, T>) -> Option<T> {
+    match token.as_ref()? {
+        Token::Value(value) => Some(*value),
+        Token::Named(name) => values.get(name).copied(),
+    }
+}
+
+/// Warns if `token` names a value that isn't in `values`.
+fn check<T>(token: &Option<Token<T>>, values: &HashMap<String, T>, kind: &str, class: &str) {
+    if let Some(Token::Named(name)) = token
+        && !values.contains_key(name)
+    {
+        log::warn!("The {:?} style uses the {} {:?}, which the theme doesn't have", class, kind, name);
+    }
+}
+
+impl Theme {
+    /// Parses a theme written in `format`.
+    pub fn parse(text: &str, format: DataFormat) -> anyhow::Result<Self> {
+        format.parse(text)
+    }
+
+    /// Loads a theme from the resources, in the format its extension names.
+    pub async fn load(file_name: &str) -> anyhow::Result<Self> {
+        format::load(file_name).await
+    }
+
+    /// Warns about every name the styles use that the theme has no value for. Those
+    /// fields are left unset when an element is styled.
+    pub fn warn_missing_tokens(&self) {
+        for (class, style) in &self.styles {
+            for color in [&style.color, &style.hovered_color, &style.pressed_color, &style.focused_color, &style.disabled_color, &style.text_color, &style.accent_color] {
+                check(color, &self.colors, "color", class);
+            }
+            check(&style.font_size, &self.font_sizes, "font size", class);
+            check(&style.corner_radius, &self.corner_radii, "corner radius", class);
+            check(&style.padding, &self.spacing, "spacing", class);
+        }
+    }
+
+    /// The style of a class of element, empty if the theme doesn't style it.
+    pub fn style(&self, class: &str) -> Style {
+        self.styles.get(class).cloned().unwrap_or_default()
+    }
+
+    pub fn color(&self, token: &Option<Token<[f32; 4]>>) -> Option<[f32; 4]> {
+        lookup(token, &self.colors)
+    }
+
+    pub fn font_size(&self, token: &Option<Token<f32>>) -> Option<f32> {
+        lookup(token, &self.font_sizes)
+    }
+
+    pub fn spacing(&self, token: &Option<Token<f32>>) -> Option<f32> {
+        lookup(token, &self.spacing)
+    }
+
+    pub fn corner_radius(&self, token: &Option<Token<f32>>) -> Option<f32> {
+        lookup(token, &self.corner_radii)
+    }
+
+    /// The UI atlas entry a skin name stands for, which is the name itself if the
+    /// theme has no skin called that.
+    pub fn skin(&self, skin: &Option<String>) -> Option<String> {
+        let skin = skin.as_ref()?;
+        Some(self.skins.get(skin).unwrap_or(skin).clone())
+    }
+}
@@ -0,0 +1,75 @@
+pub use atlas_gen::atlas::{NineSlice, PixelRect, UiAtlas, UiAtlasTexture, UvRect};
+
+use crate::{texture, utilities::resource::load_binary};
+
+/// Name of the plain white atlas entry used by elements without a sprite.
+pub const SOLID_SPRITE: &str = "solid";
+
+/// The UI atlas layout together with its pages uploaded to the GPU as one array texture.
+pub(crate) struct AtlasResources {
+    pub(crate) atlas: UiAtlas,
+    #[allow(unused)]
+    pub(crate) texture: texture::Texture,
+    pub(crate) bind_group: wgpu::BindGroup,
+}
+
+pub(crate) fn create_bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+    device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    multisampled: false,
+                    view_dimension: wgpu::TextureViewDimension::D2Array,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                },
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            },
+        ],
+        label: Some("ui_atlas_bind_group_layout"),
+    })
+}
+
+pub(crate) async fn load_ui_atlas(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    layout: &wgpu::BindGroupLayout,
+) -> anyhow::Result<AtlasResources> {
+    let atlas_data = load_binary("atlas.json").await?;
+    let atlas: UiAtlas = serde_json::from_slice(&atlas_data)?;
+
+    let mut pages = Vec::with_capacity(atlas.page_names().len());
+    for page in atlas.page_names() {
+        let image_data = load_binary(page).await?;
+        pages.push(image::load_from_memory(&image_data)?);
+    }
+    let texture = texture::Texture::from_image_layers(device, queue, &pages, Some("UI Atlas Texture"))?;
+
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("UI Atlas Bind Group"),
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&texture.view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(&texture.sampler),
+            },
+        ],
+    });
+
+    Ok(AtlasResources {
+        atlas,
+        texture,
+        bind_group,
+    })
+}
@@ -40,8 +40,7 @@ impl Camera2D {
     }
 
     pub(crate) fn build_view_projection_matrix(&self) -> Mat4 {
-        let view_proj = self.build_projection_matrix() * self.build_view_matrix();
-        view_proj
+        self.build_projection_matrix() * self.build_view_matrix()
     }
 
     pub(crate) fn update_screen_size(&mut self, new_size: PhysicalSize<u32>) {
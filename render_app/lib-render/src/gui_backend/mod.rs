@@ -1,54 +1,372 @@
-use std::collections::HashMap;
+pub(crate) mod text;
+
+pub use text::measure_text;
+
+use std::{collections::HashMap, f32::consts::TAU, ops::Range};
 
 use winit::dpi::PhysicalSize;
 
-use crate::types::{GeometryType, Instance, Vertex};
+use crate::{atlas::{NineSlice, SOLID_SPRITE, UiAtlas, UiAtlasTexture}, types::{GeometryType, Instance, StageUpdate, StagedInstance, Vertex}};
+
+/// Number of straight segments a full circle is approximated with.
+const CIRCLE_SEGMENTS: usize = 48;
+
+/// A run of consecutive instances at the same depth drawn with the same base mesh and clip region.
+#[derive(Clone, Debug)]
+pub(crate) struct DrawRange {
+    pub(crate) indices: Range<u32>,
+    pub(crate) instances: Range<u32>,
+    /// Scissor rectangle as `[x, y, width, height]` in physical pixels.
+    pub(crate) scissor: [u32; 4],
+    /// Depth of the instances, to draw text between them, see [`TextSection::depth`](crate::types::TextSection::depth).
+    pub(crate) depth: f32,
+}
+
+/// The scissor rectangle in physical pixels of a clip region given in logical pixels,
+/// limited to the window. No clip region covers the whole window.
+pub(crate) fn scissor(clip: Option<[f32; 4]>, window_size: PhysicalSize<u32>, scale_factor: f32) -> [u32; 4] {
+    let Some([x, y, width, height]) = clip else {
+        return [0, 0, window_size.width, window_size.height];
+    };
+
+    let to_pixels = |logical: f32, size: u32| (logical * scale_factor).round().clamp(0.0, size as f32) as u32;
+    let (left, right) = (to_pixels(x, window_size.width), to_pixels(x + width, window_size.width));
+    let (top, bottom) = (to_pixels(y, window_size.height), to_pixels(y + height, window_size.height));
+    [left, top, right.saturating_sub(left), bottom.saturating_sub(top)]
+}
+
+/// Vertex, index and instance data for a whole stage, merged into one set of buffers
+/// and split into draws in back to front order.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct StagedGeometry {
+    pub(crate) vertices: Vec<Vertex>,
+    pub(crate) indices: Vec<u32>,
+    pub(crate) instances: Vec<Instance>,
+    pub(crate) draws: Vec<DrawRange>,
+}
+
+/// A staged element turned into instances for the window, see
+/// [`BackendGraphicsInterface::interpret_element`].
+#[derive(Clone, Debug)]
+pub(crate) struct InterpretedElement {
+    geometry: GeometryType,
+    instances: Vec<Instance>,
+    /// Quads drawn right over the element, see [`StagedInstance::overlays`].
+    overlays: Vec<Instance>,
+    scissor: [u32; 4],
+    depth: f32,
+}
+
+/// The elements of a retained stage interpreted for the window, kept by ID so that a
+/// [`StageUpdate`] only interprets the elements it adds or changes.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct InterpretedStage {
+    elements: HashMap<u64, InterpretedElement>,
+}
+
+impl InterpretedStage {
+    /// Interprets every element of `stage`, forgetting any others, e.g. when the window
+    /// size, scale factor or atlas changed.
+    pub(crate) fn interpret_all(&mut self, stage: &[StagedInstance], window_size: PhysicalSize<u32>, scale_factor: f32, atlas: &UiAtlas) {
+        self.elements = stage
+            .iter()
+            .map(|element| (element.id, BackendGraphicsInterface::interpret_element(element, window_size, scale_factor, atlas)))
+            .collect();
+    }
+
+    /// Interprets the elements `update` adds or changes and forgets those it removes.
+    pub(crate) fn apply(&mut self, update: &StageUpdate, window_size: PhysicalSize<u32>, scale_factor: f32, atlas: &UiAtlas) {
+        for id in &update.removed {
+            self.elements.remove(id);
+        }
+        for element in &update.elements {
+            self.elements.insert(element.id, BackendGraphicsInterface::interpret_element(element, window_size, scale_factor, atlas));
+        }
+    }
+
+    /// The interpreted elements of `stage` in its order.
+    pub(crate) fn elements<'a>(&'a self, stage: &'a [StagedInstance]) -> impl Iterator<Item = &'a InterpretedElement> {
+        stage.iter().filter_map(|element| self.elements.get(&element.id))
+    }
+}
 
 pub struct BackendGraphicsInterface {
     vertex_buffer: wgpu::Buffer,
     index_buffer: wgpu::Buffer,
     instance_buffer: wgpu::Buffer,
-    index_count: usize,
-    instance_count: usize,
+    draws: Vec<DrawRange>,
+    /// What the buffers currently hold, to upload only what changed.
+    uploaded: StagedGeometry,
+}
+
+fn create_buffer(device: &wgpu::Device, label: &str, size: wgpu::BufferAddress, usage: wgpu::BufferUsages) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some(label),
+        size,
+        usage: usage | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+/// Replaces `buffer` with one at least twice as large if `data` doesn't fit. Returns
+/// whether it did, in which case the new buffer holds nothing yet.
+fn grow_buffer(device: &wgpu::Device, buffer: &mut wgpu::Buffer, label: &str, data: &[u8], usage: wgpu::BufferUsages) -> bool {
+    let needed = data.len() as wgpu::BufferAddress;
+    if needed <= buffer.size() {
+        return false;
+    }
+
+    *buffer = create_buffer(device, label, needed.max(buffer.size() * 2), usage);
+    true
+}
+
+/// Writes the span of `data` that differs from `previous`, whole items of `item_size`
+/// bytes at a time, or all of it when the length changed or `force` is set.
+fn write_changes(queue: &wgpu::Queue, buffer: &wgpu::Buffer, previous: &[u8], data: &[u8], item_size: usize, force: bool) {
+    if force || previous.len() != data.len() {
+        queue.write_buffer(buffer, 0, data);
+        return;
+    }
+
+    let Some(first) = previous.iter().zip(data).position(|(a, b)| a != b) else {
+        return;
+    };
+    let last = previous.iter().zip(data).rposition(|(a, b)| a != b).unwrap();
+    let start = first / item_size * item_size;
+    let end = (last / item_size + 1) * item_size;
+    queue.write_buffer(buffer, start as wgpu::BufferAddress, &data[start..end]);
+}
+
+fn vertex(x: f32, y: f32) -> Vertex {
+    Vertex {
+        position: [x, y, 0.0],
+        color: [1.0, 1.0, 1.0, 1.0],
+        tex_coords: [x + 0.5, y + 0.5],
+    }
+}
+
+/// Triangulates a convex outline, given clockwise on screen, as a fan around the origin.
+fn fan(outline: &[[f32; 2]]) -> (Vec<Vertex>, Vec<u32>) {
+    let mut vertices = vec![vertex(0.0, 0.0)];
+    vertices.extend(outline.iter().map(|&[x, y]| vertex(x, y)));
+
+    let count = outline.len() as u32;
+    let indices = (0..count).flat_map(|i| [0, i + 1, (i + 1) % count + 1]).collect();
+    (vertices, indices)
+}
+
+/// `segments + 1` evenly spaced points on an arc around `center`, from `start` radians
+/// clockwise through `sweep` radians.
+fn arc(center: [f32; 2], radius: f32, start: f32, sweep: f32, segments: usize) -> impl Iterator<Item = [f32; 2]> {
+    (0..=segments).map(move |i| {
+        let angle = start + sweep * i as f32 / segments as f32;
+        [center[0] + radius * angle.cos(), center[1] + radius * angle.sin()]
+    })
+}
+
+/// The geometry type whose base mesh `geometry` is drawn with. Rounded rectangles and
+/// lines are quads that the shader rounds off or the renderer rotates.
+fn mesh_type(geometry: GeometryType) -> GeometryType {
+    match geometry {
+        GeometryType::RoundedRectangle | GeometryType::Line => GeometryType::Quadrilateral,
+        geometry => geometry,
+    }
+}
+
+/// The unit mesh of a geometry type. With y pointing down, triangles wind clockwise on screen.
+fn base_mesh(geometry: GeometryType) -> (Vec<Vertex>, Vec<u32>) {
+    match mesh_type(geometry) {
+        GeometryType::Triangle => {
+            let vertices = vec![
+                vertex(0.0, -0.5),  // Top
+                vertex(0.5, 0.5),   // Bottom-right
+                vertex(-0.5, 0.5),  // Bottom-left
+            ];
+            (vertices, vec![0, 1, 2])
+        }
+        GeometryType::Circle => {
+            let outline: Vec<_> = arc([0.0, 0.0], 0.5, 0.0, TAU, CIRCLE_SEGMENTS).take(CIRCLE_SEGMENTS).collect();
+            fan(&outline)
+        }
+        _ => {
+            let vertices = vec![
+                vertex(-0.5, -0.5), // Top-left
+                vertex(0.5, -0.5),  // Top-right
+                vertex(0.5, 0.5),   // Bottom-right
+                vertex(-0.5, 0.5),  // Bottom-left
+            ];
+            (vertices, vec![0, 1, 2, 2, 3, 0])
+        }
+    }
 }
 
 impl BackendGraphicsInterface {
-    pub(crate) fn interpret_stage(stage: HashMap<GeometryType, Vec<Instance>>, window_size: PhysicalSize<u32>) -> (Vec<Vertex>, Vec<u32>, Vec<Instance>) {
-        for (geometry, elements) in stage {
-            let mut instances: Vec<Instance> = Vec::new();
+    /// Turns a staged element into instances in physical pixels for a window of
+    /// `window_size`. Everything about it is in logical pixels, multiplied by `scale_factor`.
+    pub(crate) fn interpret_element(element: &StagedInstance, window_size: PhysicalSize<u32>, scale_factor: f32, atlas: &UiAtlas) -> InterpretedElement {
+        let StagedInstance { geometry, instance, sprite, line_width, overlays, clip, .. } = element;
+        let solid_entry = atlas.get_entry_by_name(SOLID_SPRITE);
+        let (geometry, mut instance) = (*geometry, *instance);
+        let mut instances = Vec::new();
 
+        instance.position = instance.position.map(|coordinate| coordinate * scale_factor);
+        instance.scale = instance.scale.map(|size| size * scale_factor);
+        instance.corner_radius *= scale_factor;
+        if geometry == GeometryType::Line {
+            // The scale is the offset between the end points; turn it into a length and an angle.
+            let [dx, dy] = instance.scale;
+            instance.scale = [dx.hypot(dy), line_width.unwrap_or(1.0) * scale_factor];
+            instance.rotation += dy.atan2(dx);
+        }
 
-            for mut instance in elements {
-                instance.position = [instance.position[0] * window_size.width as f32, instance.position[1] * window_size.height as f32];
-                instance.scale = [instance.scale[0] * window_size.width as f32, instance.scale[1] * window_size.height as f32];
+        let entry = match sprite {
+            Some(name) => atlas.get_entry_by_name(name).or_else(|| {
+                log::warn!("No UI atlas entry named {:?}", name);
+                solid_entry
+            }),
+            None => solid_entry,
+        };
+
+        match entry {
+            Some(entry) => match entry.nine_slice {
+                Some(insets) if geometry == GeometryType::Quadrilateral => Self::push_nine_slice(&mut instances, instance, entry, insets, scale_factor, atlas),
+                _ => {
+                    (instance.tex_coords, instance.tex_page) = (entry.uv.to_array(), entry.page);
+                    instances.push(instance);
+                }
+            },
+            None => {
+                (instance.tex_coords, instance.tex_page) = ([0.0, 0.0, 1.0, 1.0], 0);
                 instances.push(instance);
             }
-            match geometry {
-                GeometryType::Quadrilateral => {
-                    let indices = [0, 1, 2, 2, 3, 0].to_vec();
-                    let vertices = [
-                        Vertex {
-                            position: [-0.5, -0.5, 0.0], // Bottom-left
-                            color: [1.0, 0.0, 0.0, 1.0],
-                        },
-                        Vertex {
-                            position: [0.5, -0.5, 0.0], // Bottom-right
-                            color: [1.0, 0.0, 0.0, 1.0],
-                        },
-                        Vertex {
-                            position: [0.5, 0.5, 0.0],  // Top-right
-                            color: [1.0, 0.0, 0.0, 1.0],
-                        },
-                        Vertex {
-                            position: [-0.5, 0.5, 0.0], // Top-left
-                            color: [1.0, 0.0, 0.0, 1.0],
-                        }
-                    ].to_vec();
-                    return (vertices, indices, instances)
+        }
+
+        let (tex_coords, tex_page) = solid_entry.map_or(([0.0, 0.0, 1.0, 1.0], 0), |entry| (entry.uv.to_array(), entry.page));
+        let overlays = overlays.iter().map(|overlay| Instance {
+            position: overlay.position.map(|coordinate| coordinate * scale_factor),
+            scale: overlay.scale.map(|size| size * scale_factor),
+            corner_radius: overlay.corner_radius * scale_factor,
+            tex_coords,
+            tex_page,
+            ..*overlay
+        }).collect();
+
+        InterpretedElement {
+            geometry,
+            instances,
+            overlays,
+            scissor: scissor(*clip, window_size, scale_factor),
+            depth: instance.depth,
+        }
+    }
+
+    /// Merges interpreted elements into one set of buffers, split into draws back to front.
+    pub(crate) fn assemble<'a>(elements: impl IntoIterator<Item = &'a InterpretedElement>) -> StagedGeometry {
+        let mut staged = StagedGeometry::default();
+        // Where each base mesh landed in the shared buffers, uploaded the first time it is used.
+        let mut meshes: HashMap<GeometryType, Range<u32>> = HashMap::new();
+
+        // Painter's order: a stable sort keeps elements at the same depth in the order they were added.
+        let mut ordered: Vec<&InterpretedElement> = elements.into_iter().collect();
+        ordered.sort_by(|a, b| a.depth.total_cmp(&b.depth));
+
+        for element in ordered {
+            let indices = Self::mesh(&mut staged, &mut meshes, element.geometry);
+            let first_instance = staged.instances.len() as u32;
+            staged.instances.extend_from_slice(&element.instances);
+            Self::push_draw(&mut staged, indices, first_instance, element.scissor, element.depth);
+
+            if !element.overlays.is_empty() {
+                let indices = Self::mesh(&mut staged, &mut meshes, GeometryType::Quadrilateral);
+                let first_instance = staged.instances.len() as u32;
+                staged.instances.extend_from_slice(&element.overlays);
+                Self::push_draw(&mut staged, indices, first_instance, element.scissor, element.depth);
+            }
+        }
+        staged
+    }
+
+    /// Where the indices of the base mesh of `geometry` are in the staged buffers, adding it the
+    /// first time. The indices are offset to the mesh's vertices rather than relying on a base
+    /// vertex, which WebGL2 doesn't support.
+    fn mesh(staged: &mut StagedGeometry, meshes: &mut HashMap<GeometryType, Range<u32>>, geometry: GeometryType) -> Range<u32> {
+        meshes
+            .entry(mesh_type(geometry))
+            .or_insert_with(|| {
+                let (vertices, indices) = base_mesh(geometry);
+                let base_vertex = staged.vertices.len() as u32;
+                let first_index = staged.indices.len() as u32;
+                staged.vertices.extend(vertices);
+                staged.indices.extend(indices.into_iter().map(|index| index + base_vertex));
+                first_index..staged.indices.len() as u32
+            })
+            .clone()
+    }
+
+    /// Draws the instances from `first_instance` on with the given mesh, clipped to
+    /// `scissor`. Consecutive instances sharing a mesh, clip region and depth are drawn together.
+    fn push_draw(staged: &mut StagedGeometry, indices: Range<u32>, first_instance: u32, scissor: [u32; 4], depth: f32) {
+        let instances_end = staged.instances.len() as u32;
+        match staged.draws.last_mut() {
+            Some(draw) if draw.indices == indices && draw.scissor == scissor && draw.depth == depth && draw.instances.end == first_instance => draw.instances.end = instances_end,
+            _ => staged.draws.push(DrawRange {
+                indices,
+                instances: first_instance..instances_end,
+                scissor,
+                depth,
+            }),
+        }
+    }
+
+    /// Splits `instance` into up to nine quads so the corners of `entry` keep their
+    /// size in logical pixels, the edges stretch along one axis and the centre along both.
+    ///
+    /// Rows and columns with a zero inset are skipped. When the quad is smaller than
+    /// the insets, they shrink proportionally so the corners never overlap. The skin's
+    /// own corners replace any corner radius.
+    fn push_nine_slice(instances: &mut Vec<Instance>, instance: Instance, entry: &UiAtlasTexture, insets: NineSlice, scale_factor: f32, atlas: &UiAtlas) {
+        let uv = entry.uv;
+        let size = [instance.scale[0].abs(), instance.scale[1].abs()];
+        let min = [instance.position[0] - size[0] / 2.0, instance.position[1] - size[1] / 2.0];
+
+        // Edges of the three columns (x) and rows (y), in screen pixels and in texture coordinates.
+        let slice_edges = |axis: usize, start: u32, end: u32, atlas_size: u32| {
+            let (start, end) = (start as f32, end as f32);
+            let shrink = (size[axis] / ((start + end) * scale_factor)).min(1.0) * scale_factor;
+            let positions = [min[axis], min[axis] + start * shrink, min[axis] + size[axis] - end * shrink, min[axis] + size[axis]];
+            let tex_coords = [uv.min[axis], uv.min[axis] + start / atlas_size as f32, uv.max[axis] - end / atlas_size as f32, uv.max[axis]];
+            (positions, tex_coords)
+        };
+        let (x, u) = slice_edges(0, insets.left, insets.right, atlas.width());
+        let (y, v) = slice_edges(1, insets.top, insets.bottom, atlas.height());
+        let (sin, cos) = instance.rotation.sin_cos();
+        let columns = [insets.left > 0, true, insets.right > 0];
+        let rows = [insets.top > 0, true, insets.bottom > 0];
+
+        for row in 0..3 {
+            for column in 0..3 {
+                if !rows[row] || !columns[column] {
+                    continue;
                 }
+
+                // Rotate each slice around the centre of the whole element.
+                let offset = [
+                    (x[column] + x[column + 1]) / 2.0 - instance.position[0],
+                    (y[row] + y[row + 1]) / 2.0 - instance.position[1],
+                ];
+                instances.push(Instance {
+                    position: [
+                        instance.position[0] + offset[0] * cos - offset[1] * sin,
+                        instance.position[1] + offset[0] * sin + offset[1] * cos,
+                    ],
+                    scale: [x[column + 1] - x[column], y[row + 1] - y[row]],
+                    tex_coords: [u[column], v[row], u[column + 1], v[row + 1]],
+                    tex_page: entry.page,
+                    corner_radius: 0.0,
+                    ..instance
+                });
             }
         }
-        (Vec::new(), Vec::new(), Vec::new())
     }
 
     pub fn initialize_buffers(device: &wgpu::Device, vertex_count: usize, index_count: usize, instance_count: usize) -> Self {
@@ -56,50 +374,52 @@ impl BackendGraphicsInterface {
         let index_buffer_size = (index_count * std::mem::size_of::<u32>()) as wgpu::BufferAddress;
         let instance_buffer_size = (instance_count * std::mem::size_of::<Instance>()) as wgpu::BufferAddress;
 
-        let vertex_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("Vertex Buffer"),
-            size: vertex_buffer_size,
-            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
-            mapped_at_creation: false,
-        });
-
-        let index_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("Index Buffer"),
-            size: index_buffer_size,
-            usage: wgpu::BufferUsages::INDEX | wgpu::BufferUsages::COPY_DST,
-            mapped_at_creation: false,
-        });
-
-        let instance_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("Instance Buffer"),
-            size: instance_buffer_size,
-            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
-            mapped_at_creation: false,
-        });
-
         Self {
-            vertex_buffer,
-            index_buffer,
-            instance_buffer,
-            index_count,
-            instance_count,
+            vertex_buffer: create_buffer(device, "Vertex Buffer", vertex_buffer_size, wgpu::BufferUsages::VERTEX),
+            index_buffer: create_buffer(device, "Index Buffer", index_buffer_size, wgpu::BufferUsages::INDEX),
+            instance_buffer: create_buffer(device, "Instance Buffer", instance_buffer_size, wgpu::BufferUsages::VERTEX),
+            draws: Vec::new(),
+            uploaded: StagedGeometry::default(),
         }
     }
 
-    pub fn update_buffer_data(&mut self, queue: &wgpu::Queue, vertices: &[Vertex], indices: &[u32], instances: &[Instance]) {
-        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(vertices));
-        queue.write_buffer(&self.index_buffer, 0, bytemuck::cast_slice(indices));
-        queue.write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(instances));
+    /// Uploads `staged`, writing only the parts that differ from the last upload and
+    /// growing any buffer that is too small to hold it.
+    pub(crate) fn update_buffer_data(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, staged: &StagedGeometry) {
+        let vertex_grown = grow_buffer(device, &mut self.vertex_buffer, "Vertex Buffer", bytemuck::cast_slice(&staged.vertices), wgpu::BufferUsages::VERTEX);
+        let index_grown = grow_buffer(device, &mut self.index_buffer, "Index Buffer", bytemuck::cast_slice(&staged.indices), wgpu::BufferUsages::INDEX);
+        let instance_grown = grow_buffer(device, &mut self.instance_buffer, "Instance Buffer", bytemuck::cast_slice(&staged.instances), wgpu::BufferUsages::VERTEX);
+
+        let uploaded = std::mem::take(&mut self.uploaded);
+        write_changes(queue, &self.vertex_buffer, bytemuck::cast_slice(&uploaded.vertices), bytemuck::cast_slice(&staged.vertices), size_of::<Vertex>(), vertex_grown);
+        write_changes(queue, &self.index_buffer, bytemuck::cast_slice(&uploaded.indices), bytemuck::cast_slice(&staged.indices), size_of::<u32>(), index_grown);
+        write_changes(queue, &self.instance_buffer, bytemuck::cast_slice(&uploaded.instances), bytemuck::cast_slice(&staged.instances), size_of::<Instance>(), instance_grown);
+
+        self.draws = staged.draws.clone();
+        self.uploaded = staged.clone();
+    }
+
+    /// The depth of each draw, back to front.
+    pub(crate) fn draw_depths(&self) -> Vec<f32> {
+        self.draws.iter().map(|draw| draw.depth).collect()
     }
 
-    pub fn render(&self, render_pass: &mut wgpu::RenderPass) {
-        if self.vertex_buffer.size() == 0 {
+    /// Issues the given draws, numbered as in [`Self::draw_depths`].
+    pub fn render(&self, render_pass: &mut wgpu::RenderPass, draws: Range<usize>) {
+        if draws.is_empty() {
             return;
-        } else {
-            render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-            render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
-            render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
-            render_pass.draw_indexed(0..self.index_count as u32, 0, 0..self.instance_count as u32);
+        }
+
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
+        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        for draw in &self.draws[draws] {
+            let [x, y, width, height] = draw.scissor;
+            if width == 0 || height == 0 {
+                continue;
+            }
+            render_pass.set_scissor_rect(x, y, width, height);
+            render_pass.draw_indexed(draw.indices.clone(), 0, draw.instances.clone());
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,490 @@
+use std::{collections::HashMap, ops::Range, sync::LazyLock};
+
+use ab_glyph::{Font, FontRef, GlyphId, PxScale, ScaleFont, point};
+use winit::dpi::PhysicalSize;
+
+use crate::{gui_backend::scissor, types::TextSection};
+
+const FONT_DATA: &[u8] = include_bytes!("../../../ComicMono.ttf");
+const GLYPH_CACHE_SIZE: u32 = 1024;
+// Empty texels kept around every glyph so linear filtering never samples a neighbour.
+const GLYPH_PADDING: u32 = 1;
+// Glyph instances the instance buffer starts with, so it is never empty.
+const MIN_INSTANCE_CAPACITY: usize = 256;
+
+/// The bundled font, parsed on first use.
+static FONT: LazyLock<FontRef<'static>> = LazyLock::new(|| FontRef::try_from_slice(FONT_DATA).expect("the bundled font is valid"));
+
+/// Width and height in pixels of `text` at a font height of `size` pixels, laid out the
+/// way [`TextRenderer`] draws it.
+pub fn measure_text(text: &str, size: f32) -> [f32; 2] {
+    let scaled_font = FONT.as_scaled(PxScale::from(size));
+
+    let mut width: f32 = 0.0;
+    let mut lines = 0;
+    for line in text.split('\n') {
+        let mut caret = 0.0;
+        let mut previous: Option<GlyphId> = None;
+        for character in line.chars() {
+            let glyph_id = scaled_font.glyph_id(character);
+            if let Some(previous) = previous {
+                caret += scaled_font.kern(previous, glyph_id);
+            }
+            caret += scaled_font.h_advance(glyph_id);
+            previous = Some(glyph_id);
+        }
+        width = width.max(caret);
+        lines += 1;
+    }
+
+    let line_height = scaled_font.height() + scaled_font.line_gap();
+    [width, scaled_font.height() + line_height * (lines - 1) as f32]
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct GlyphVertex {
+    pub corner: [f32; 2],
+}
+
+impl GlyphVertex {
+    pub(crate) fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<GlyphVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 0,
+                    format: wgpu::VertexFormat::Float32x2,
+                }
+            ]
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub(crate) struct GlyphInstance {
+    pub position: [f32; 2],
+    pub size: [f32; 2],
+    pub tex_min: [f32; 2],
+    pub tex_max: [f32; 2],
+    pub color: [f32; 4],
+}
+
+impl GlyphInstance {
+    pub(crate) fn desc() -> wgpu::VertexBufferLayout<'static> {
+        use std::mem;
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<GlyphInstance>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &[
+                wgpu::VertexAttribute {
+                    offset: 0,
+                    shader_location: 1,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 2]>() as wgpu::BufferAddress,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
+                    shader_location: 3,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 6]>() as wgpu::BufferAddress,
+                    shader_location: 4,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 8]>() as wgpu::BufferAddress,
+                    shader_location: 5,
+                    format: wgpu::VertexFormat::Float32x4,
+                }
+            ]
+        }
+    }
+}
+
+/// Location of a rasterized glyph inside the cache texture.
+#[derive(Copy, Clone, Debug)]
+struct CachedGlyph {
+    // Offset of the bitmap's top-left corner from the pen position on the baseline.
+    offset: [f32; 2],
+    size: [f32; 2],
+    tex_min: [f32; 2],
+    tex_max: [f32; 2],
+}
+
+/// A single-channel texture that glyphs are rasterized into on first use.
+///
+/// Glyphs are packed in rows ("shelves"); a glyph that doesn't fit is skipped and
+/// marks the cache as overflowed, for [`TextRenderer::prepare`] to clear it and
+/// rasterize only the glyphs still drawn.
+struct GlyphCache {
+    texture: wgpu::Texture,
+    glyphs: HashMap<(GlyphId, u32), Option<CachedGlyph>>,
+    cursor_x: u32,
+    cursor_y: u32,
+    row_height: u32,
+    overflowed: bool,
+}
+
+impl GlyphCache {
+    fn new(device: &wgpu::Device) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Glyph Cache Texture"),
+            size: wgpu::Extent3d {
+                width: GLYPH_CACHE_SIZE,
+                height: GLYPH_CACHE_SIZE,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        Self {
+            texture,
+            glyphs: HashMap::new(),
+            cursor_x: 0,
+            cursor_y: 0,
+            row_height: 0,
+            overflowed: false,
+        }
+    }
+
+    /// Forgets every glyph, so the texture is filled again from the top.
+    fn clear(&mut self) {
+        self.glyphs.clear();
+        self.cursor_x = 0;
+        self.cursor_y = 0;
+        self.row_height = 0;
+        self.overflowed = false;
+    }
+
+    fn allocate(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        let padded_width = width + GLYPH_PADDING * 2;
+        let padded_height = height + GLYPH_PADDING * 2;
+
+        if self.cursor_x + padded_width > GLYPH_CACHE_SIZE {
+            self.cursor_x = 0;
+            self.cursor_y += self.row_height;
+            self.row_height = 0;
+        }
+
+        if padded_width > GLYPH_CACHE_SIZE || self.cursor_y + padded_height > GLYPH_CACHE_SIZE {
+            return None;
+        }
+
+        let origin = (self.cursor_x + GLYPH_PADDING, self.cursor_y + GLYPH_PADDING);
+        self.cursor_x += padded_width;
+        self.row_height = self.row_height.max(padded_height);
+        Some(origin)
+    }
+
+    fn get_or_rasterize(&mut self, queue: &wgpu::Queue, glyph_id: GlyphId, size: f32) -> Option<CachedGlyph> {
+        let key = (glyph_id, size.to_bits());
+        if let Some(cached) = self.glyphs.get(&key) {
+            return *cached;
+        }
+
+        let cached = FONT
+            .outline_glyph(glyph_id.with_scale_and_position(PxScale::from(size), point(0.0, 0.0)))
+            .and_then(|outline| {
+                let bounds = outline.px_bounds();
+                let width = bounds.width() as u32;
+                let height = bounds.height() as u32;
+                if width == 0 || height == 0 {
+                    return None;
+                }
+
+                let Some((x, y)) = self.allocate(width, height) else {
+                    self.overflowed = true;
+                    return None;
+                };
+
+                let mut coverage = vec![0u8; (width * height) as usize];
+                outline.draw(|gx, gy, c| {
+                    if gx < width && gy < height {
+                        coverage[(gy * width + gx) as usize] = (c.clamp(0.0, 1.0) * 255.0) as u8;
+                    }
+                });
+
+                queue.write_texture(
+                    wgpu::TexelCopyTextureInfo {
+                        aspect: wgpu::TextureAspect::All,
+                        texture: &self.texture,
+                        mip_level: 0,
+                        origin: wgpu::Origin3d { x, y, z: 0 },
+                    },
+                    &coverage,
+                    wgpu::TexelCopyBufferLayout {
+                        offset: 0,
+                        bytes_per_row: Some(width),
+                        rows_per_image: Some(height),
+                    },
+                    wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+                );
+
+                let atlas_size = GLYPH_CACHE_SIZE as f32;
+                Some(CachedGlyph {
+                    offset: [bounds.min.x, bounds.min.y],
+                    size: [width as f32, height as f32],
+                    tex_min: [x as f32 / atlas_size, y as f32 / atlas_size],
+                    tex_max: [(x + width) as f32 / atlas_size, (y + height) as f32 / atlas_size],
+                })
+            });
+
+        self.glyphs.insert(key, cached);
+        cached
+    }
+}
+
+/// Glyph quads laid out for some text sections, with the draws that clip them.
+#[derive(Default)]
+struct TextLayout {
+    instances: Vec<GlyphInstance>,
+    /// Runs of consecutive glyph instances that share a scissor rectangle and depth, back to front.
+    draws: Vec<(Range<u32>, [u32; 4], f32)>,
+}
+
+pub(crate) struct TextRenderer {
+    glyph_cache: GlyphCache,
+    /// The scale factor the cached glyphs were rasterized for.
+    scale_factor: f32,
+    bind_group: wgpu::BindGroup,
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    instance_buffer: wgpu::Buffer,
+    /// The text of the retained interface, kept until it changes.
+    retained_sections: Vec<TextSection>,
+    retained: TextLayout,
+    /// The text of the immediate-mode interface, uploaded after the retained text.
+    immediate: TextLayout,
+}
+
+impl TextRenderer {
+    pub(crate) fn new(device: &wgpu::Device, texture_bind_group_layout: &wgpu::BindGroupLayout) -> Self {
+        use wgpu::util::DeviceExt;
+
+        let glyph_cache = GlyphCache::new(device);
+
+        let view = glyph_cache.texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Glyph Cache Bind Group"),
+            layout: texture_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+        });
+
+        let vertices = [
+            GlyphVertex { corner: [0.0, 0.0] }, // Top-left
+            GlyphVertex { corner: [1.0, 0.0] }, // Top-right
+            GlyphVertex { corner: [1.0, 1.0] }, // Bottom-right
+            GlyphVertex { corner: [0.0, 1.0] }, // Bottom-left
+        ];
+        let indices: [u32; 6] = [0, 1, 2, 2, 3, 0];
+
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Glyph Vertex Buffer"),
+            contents: bytemuck::cast_slice(&vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Glyph Index Buffer"),
+            contents: bytemuck::cast_slice(&indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+        let instance_buffer = Self::create_instance_buffer(device, MIN_INSTANCE_CAPACITY);
+
+        Self {
+            glyph_cache,
+            scale_factor: 0.0,
+            bind_group,
+            vertex_buffer,
+            index_buffer,
+            instance_buffer,
+            retained_sections: Vec::new(),
+            retained: TextLayout::default(),
+            immediate: TextLayout::default(),
+        }
+    }
+
+    fn create_instance_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Glyph Instance Buffer"),
+            size: (capacity * std::mem::size_of::<GlyphInstance>()) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    /// Lays out the `immediate` sections into glyph quads, and the `retained` ones if
+    /// given, and uploads them, rasterizing any glyph that is not in the cache yet. The
+    /// last retained sections given are kept. Positions, clip regions and font sizes are in
+    /// logical pixels, multiplied by `scale_factor`.
+    ///
+    /// The cache is cleared when the scale factor changes, and when it fills up, after
+    /// which only the glyphs of the retained and immediate sections are rasterized again.
+    pub(crate) fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, retained: Option<&[TextSection]>, immediate: &[TextSection], window_size: PhysicalSize<u32>, scale_factor: f32) {
+        let mut retained_changed = retained.is_some();
+        if let Some(retained) = retained {
+            self.retained_sections = retained.to_vec();
+        }
+        if scale_factor != self.scale_factor {
+            self.glyph_cache.clear();
+            self.scale_factor = scale_factor;
+            retained_changed = true;
+        }
+
+        let was_empty = self.glyph_cache.glyphs.is_empty();
+        self.glyph_cache.overflowed = false;
+        let retained = std::mem::take(&mut self.retained_sections);
+        if retained_changed {
+            self.retained = self.layout(queue, &retained, window_size, scale_factor);
+        }
+        self.immediate = self.layout(queue, immediate, window_size, scale_factor);
+        if self.glyph_cache.overflowed && !was_empty {
+            self.glyph_cache.clear();
+            self.retained = self.layout(queue, &retained, window_size, scale_factor);
+            self.immediate = self.layout(queue, immediate, window_size, scale_factor);
+            retained_changed = true;
+        }
+        self.retained_sections = retained;
+        if self.glyph_cache.overflowed {
+            log::warn!("The glyph cache can't fit all the text, some glyphs are skipped");
+        }
+
+        let retained_size = std::mem::size_of_val(self.retained.instances.as_slice()) as wgpu::BufferAddress;
+        let required_size = retained_size + std::mem::size_of_val(self.immediate.instances.as_slice()) as wgpu::BufferAddress;
+        if required_size > self.instance_buffer.size() {
+            self.instance_buffer = Self::create_instance_buffer(device, (self.retained.instances.len() + self.immediate.instances.len()).max(MIN_INSTANCE_CAPACITY));
+            retained_changed = true;
+        }
+        if retained_changed {
+            queue.write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(&self.retained.instances));
+        }
+        queue.write_buffer(&self.instance_buffer, retained_size, bytemuck::cast_slice(&self.immediate.instances));
+    }
+
+    /// Lays out every section into glyph quads, recording the draws that clip them.
+    /// Sections without any glyph don't get a draw.
+    /// Marks the glyph cache as overflowed if it couldn't fit a new glyph.
+    fn layout(&mut self, queue: &wgpu::Queue, sections: &[TextSection], window_size: PhysicalSize<u32>, scale_factor: f32) -> TextLayout {
+        let mut layout = TextLayout::default();
+        let instances = &mut layout.instances;
+
+        // Back to front like the elements, keeping the order of sections at the same depth.
+        let mut ordered: Vec<&TextSection> = sections.iter().collect();
+        ordered.sort_by(|a, b| a.depth.total_cmp(&b.depth));
+
+        for section in ordered {
+            let first_instance = instances.len() as u32;
+            let size = section.size * scale_factor;
+            let scaled_font = FONT.as_scaled(PxScale::from(size));
+            let bounds = measure_text(&section.text, size);
+            let origin_x = section.position[0] * scale_factor - section.pivot[0] * bounds[0];
+            let origin_y = section.position[1] * scale_factor - section.pivot[1] * bounds[1];
+
+            let mut caret = point(origin_x, origin_y + scaled_font.ascent());
+            let mut previous: Option<GlyphId> = None;
+
+            for character in section.text.chars() {
+                if character == '\n' {
+                    caret.x = origin_x;
+                    caret.y += scaled_font.height() + scaled_font.line_gap();
+                    previous = None;
+                    continue;
+                }
+
+                let glyph_id = scaled_font.glyph_id(character);
+                if let Some(previous) = previous {
+                    caret.x += scaled_font.kern(previous, glyph_id);
+                }
+
+                if let Some(glyph) = self.glyph_cache.get_or_rasterize(queue, glyph_id, size) {
+                    instances.push(GlyphInstance {
+                        position: [(caret.x + glyph.offset[0]).round(), (caret.y + glyph.offset[1]).round()],
+                        size: glyph.size,
+                        tex_min: glyph.tex_min,
+                        tex_max: glyph.tex_max,
+                        color: section.color,
+                    });
+                }
+
+                caret.x += scaled_font.h_advance(glyph_id);
+                previous = Some(glyph_id);
+            }
+
+            let scissor = scissor(section.clip, window_size, scale_factor);
+            let instances_end = instances.len() as u32;
+            if instances_end == first_instance {
+                continue;
+            }
+            match layout.draws.last_mut() {
+                Some((range, last, depth)) if *last == scissor && *depth == section.depth && range.end == first_instance => range.end = instances_end,
+                _ => layout.draws.push((first_instance..instances_end, scissor, section.depth)),
+            }
+        }
+
+        layout
+    }
+
+    /// The draws of the retained text and then the immediate text, with instances
+    /// numbered as in the instance buffer.
+    fn draws(&self) -> impl Iterator<Item = (Range<u32>, [u32; 4], f32)> + '_ {
+        let offset = self.retained.instances.len() as u32;
+        let immediate = self.immediate.draws.iter().map(move |(instances, scissor, depth)| (instances.start + offset..instances.end + offset, *scissor, *depth));
+        self.retained.draws.iter().cloned().chain(immediate)
+    }
+
+    /// The depth of each draw. The retained and the immediate text are each in back to
+    /// front order.
+    pub(crate) fn draw_depths(&self) -> Vec<f32> {
+        self.draws().map(|(_, _, depth)| depth).collect()
+    }
+
+    /// Issues the given draws, numbered as in [`Self::draw_depths`].
+    pub(crate) fn render(&self, render_pass: &mut wgpu::RenderPass, draws: Range<usize>) {
+        if draws.is_empty() {
+            return;
+        }
+
+        render_pass.set_bind_group(1, &self.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
+        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        for (instances, [x, y, width, height], _) in self.draws().skip(draws.start).take(draws.len()) {
+            if instances.is_empty() || width == 0 || height == 0 {
+                continue;
+            }
+            render_pass.set_scissor_rect(x, y, width, height);
+            render_pass.draw_indexed(0..6, 0, instances);
+        }
+    }
+}
@@ -1,18 +1,21 @@
-use std::{collections::HashMap, iter, sync::Arc};
+use std::{iter, sync::Arc};
 
 pub mod types;
 pub mod gui_backend;
+pub mod atlas;
 mod camera;
 mod models;
 mod texture;
-mod utilities;
+pub mod utilities;
 
 use wgpu::util::DeviceExt;
 use winit::{
-    dpi::PhysicalSize, event_loop::ActiveEventLoop, keyboard::KeyCode, window::Window
+    dpi::PhysicalSize, window::Window
 };
 
-use crate::{camera::{Camera2D, Camera2DUniform, Camera3D, Camera3DUniform}, gui_backend::BackendGraphicsInterface, models::{DrawModel, model, types::ModelVertex}, types::{GeometryType, Instance, Vertex}, utilities::pipeline::PipeLineBuilder};
+use crate::{atlas::AtlasResources, camera::{Camera2D, Camera2DUniform, Camera3D, Camera3DUniform}, gui_backend::{BackendGraphicsInterface, InterpretedStage, text::{GlyphInstance, GlyphVertex, TextRenderer}}, models::{DrawModel, model, types::ModelVertex}, types::{Instance, Stage, StageUpdate, Vertex}, utilities::pipeline::PipeLineBuilder};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::utilities::hot_reload::{ResourceKind, ResourceWatcher};
 
 pub struct State {
     surface: wgpu::Surface<'static>,
@@ -22,6 +25,7 @@ pub struct State {
     is_surface_configured: bool,
 
     ui_render_pipeline: wgpu::RenderPipeline,
+    text_render_pipeline: wgpu::RenderPipeline,
     pub window: Arc<Window>,
 
     ui_camera: Camera2D,
@@ -33,18 +37,156 @@ pub struct State {
     model_camera_bind_group: wgpu::BindGroup,
 
     backend_graphics_interface: BackendGraphicsInterface,
-    staged_ui_data: HashMap<GeometryType, Vec<Instance>>,
+    /// The geometry of the immediate-mode interface, rebuilt every frame and drawn
+    /// together with the retained geometry.
+    immediate_graphics_interface: BackendGraphicsInterface,
+    ui_atlas: AtlasResources,
+    staged_ui_data: Stage,
+    /// The staged elements interpreted for the window, kept between stage updates.
+    interpreted_ui: InterpretedStage,
+    immediate_ui_data: Stage,
+    text_renderer: TextRenderer,
+    /// Physical pixels per logical pixel of the window.
+    scale_factor: f32,
 
     model_render_pipeline: wgpu::RenderPipeline,
+    stencil_mask_pipeline: wgpu::RenderPipeline,
+    stencil_draw_pipeline: wgpu::RenderPipeline,
+    depth_texture: texture::Texture,
     obj_model: model::Model,
     obj_model_outer: model::Model,
 
     rotation_angle: f32,
+
+    bind_group_layouts: BindGroupLayouts,
+    /// Reports changed resources to reload, if the resources directory can be watched.
+    #[cfg(not(target_arch = "wasm32"))]
+    resource_watcher: Option<ResourceWatcher>,
+}
+
+/// The layouts of the bind groups the pipelines are built with.
+struct BindGroupLayouts {
+    camera: wgpu::BindGroupLayout,
+    texture: wgpu::BindGroupLayout,
+    atlas: wgpu::BindGroupLayout,
+}
+
+/// The pipelines of a [`State`], each built from its own shader.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum PipelineKind {
+    Ui,
+    Text,
+    Model,
+    /// Writes the stencil mask the model is clipped to, without drawing any color.
+    StencilMask,
+    /// Draws the model where the stencil mask was written.
+    StencilDraw,
+}
+
+impl PipelineKind {
+    #[cfg(not(target_arch = "wasm32"))]
+    const ALL: [PipelineKind; 5] = [PipelineKind::Ui, PipelineKind::Text, PipelineKind::Model, PipelineKind::StencilMask, PipelineKind::StencilDraw];
+
+    fn shader_file(self) -> &'static str {
+        match self {
+            PipelineKind::Ui => "ui_shader.wgsl",
+            PipelineKind::Text => "text_shader.wgsl",
+            PipelineKind::Model | PipelineKind::StencilMask | PipelineKind::StencilDraw => "model_shader.wgsl",
+        }
+    }
+
+    /// Nothing is depth tested, but every pipeline has to match the pass's depth-stencil
+    /// attachment, and the stencil pipelines mask the model with it.
+    fn depth_stencil(self) -> wgpu::DepthStencilState {
+        let stencil = match self {
+            PipelineKind::StencilMask => stencil_state(wgpu::CompareFunction::Always, wgpu::StencilOperation::Replace, 0xff),
+            PipelineKind::StencilDraw => stencil_state(wgpu::CompareFunction::Equal, wgpu::StencilOperation::Keep, 0x00),
+            _ => wgpu::StencilState::default(),
+        };
+        wgpu::DepthStencilState {
+            format: texture::DEPTH_FORMAT,
+            depth_write_enabled: false,
+            depth_compare: wgpu::CompareFunction::Always,
+            stencil,
+            bias: wgpu::DepthBiasState::default(),
+        }
+    }
+
+    async fn build(self, device: &wgpu::Device, layouts: &BindGroupLayouts, surface_format: wgpu::TextureFormat) -> anyhow::Result<wgpu::RenderPipeline> {
+        let mut builder = PipeLineBuilder::new(device);
+        builder
+            .set_shader_module(self.shader_file(), "vs_main", "fs_main")
+            .set_pixel_format(surface_format)
+            .set_depth_stencil(self.depth_stencil());
+        match self {
+            PipelineKind::Ui => builder
+                .add_bind_group_layout(&layouts.camera)
+                .add_bind_group_layout(&layouts.atlas)
+                .add_vertex_buffer_layout(Vertex::desc())
+                .add_vertex_buffer_layout(Instance::desc())
+                .build("UI Render Pipeline").await,
+            PipelineKind::Text => builder
+                .add_bind_group_layout(&layouts.camera)
+                .add_bind_group_layout(&layouts.texture)
+                .add_vertex_buffer_layout(GlyphVertex::desc())
+                .add_vertex_buffer_layout(GlyphInstance::desc())
+                .build("Text Render Pipeline").await,
+            PipelineKind::Model => builder
+                .add_bind_group_layout(&layouts.texture)
+                .add_bind_group_layout(&layouts.camera)
+                .add_vertex_buffer_layout(ModelVertex::desc())
+                .build("Model Render Pipeline").await,
+            PipelineKind::StencilMask => builder
+                .add_bind_group_layout(&layouts.texture)
+                .add_bind_group_layout(&layouts.camera)
+                .add_vertex_buffer_layout(ModelVertex::desc())
+                .set_write_mask(wgpu::ColorWrites::empty())
+                .build("Stencil Mask Pipeline").await,
+            PipelineKind::StencilDraw => builder
+                .add_bind_group_layout(&layouts.texture)
+                .add_bind_group_layout(&layouts.camera)
+                .add_vertex_buffer_layout(ModelVertex::desc())
+                .build("Stencil Draw Pipeline").await,
+        }
+    }
+}
+
+/// Compares the stencil reference with the buffer's value, applying `pass_op` where the
+/// comparison passes and writing the bits in `write_mask`.
+fn stencil_state(compare: wgpu::CompareFunction, pass_op: wgpu::StencilOperation, write_mask: u32) -> wgpu::StencilState {
+    let face = wgpu::StencilFaceState {
+        compare,
+        fail_op: wgpu::StencilOperation::Keep,
+        depth_fail_op: wgpu::StencilOperation::Keep,
+        pass_op,
+    };
+    wgpu::StencilState {
+        front: face,
+        back: face,
+        read_mask: 0xff,
+        write_mask,
+    }
+}
+
+/// The kinds of user interface draws, in the order they are drawn at the same depth.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum UiLayer {
+    Retained,
+    Immediate,
+    Text,
+}
+
+/// Loads the model drawn in the scene, as the inner and outer copies drawn for the stencil passes.
+async fn load_models(device: &wgpu::Device, queue: &wgpu::Queue, layout: &wgpu::BindGroupLayout) -> anyhow::Result<(model::Model, model::Model)> {
+    let obj_model = model::load_model("a.obj", device, queue, layout, 0.99, [1.0, 0.0, 0.0, 0.5]).await?;
+    let obj_model_outer = model::load_model("a.obj", device, queue, layout, 1.0, [0.0, 1.0, 0.0, 0.5]).await?;
+    Ok((obj_model, obj_model_outer))
 }
 
 impl State {
-    pub async fn new(window: Arc<Window>, staged_ui_data: HashMap<GeometryType, Vec<Instance>>) -> anyhow::Result<State> {
+    pub async fn new(window: Arc<Window>, staged_ui_data: Stage) -> anyhow::Result<State> {
         let window_size = window.inner_size();
+        let scale_factor = window.scale_factor() as f32;
 
         // The instance is a handle to our GPU
         // BackendBit::PRIMARY => Vulkan + Metal + DX12 + Browser WebGPU
@@ -84,9 +226,6 @@ impl State {
             .await
             .unwrap();
 
-        let (vertices, indices, instances) = BackendGraphicsInterface::interpret_stage(staged_ui_data.clone(), window_size);
-        let mut backend_graphics_interface = BackendGraphicsInterface::initialize_buffers(&device, vertices.len(), indices.len(), instances.len());
-
         let surface_caps = surface.get_capabilities(&adapter);
         // Shader code in this tutorial assumes an Srgb surface texture. Using a different
         // one will result all the colors comming out darker. If you want to support non
@@ -187,95 +326,34 @@ impl State {
                 label: Some("texture_bind_group_layout"),
             });
 
-        let obj_model = model::load_model("a.obj", &device, &queue, &texture_bind_group_layout, 0.99, [1.0, 0.0, 0.0, 0.5]).await.unwrap();
-        let obj_model_outer = model::load_model("a.obj", &device, &queue, &texture_bind_group_layout, 1.0, [0.0, 1.0, 0.0, 0.5]).await.unwrap();
+        let atlas_bind_group_layout = atlas::create_bind_group_layout(&device);
+        let ui_atlas = atlas::load_ui_atlas(&device, &queue, &atlas_bind_group_layout).await?;
 
-        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/ui_shader.wgsl").into()),
-        });
+        let mut interpreted_ui = InterpretedStage::default();
+        interpreted_ui.interpret_all(&staged_ui_data.elements, window_size, scale_factor, &ui_atlas.atlas);
+        let staged_geometry = BackendGraphicsInterface::assemble(interpreted_ui.elements(&staged_ui_data.elements));
+        let mut backend_graphics_interface = BackendGraphicsInterface::initialize_buffers(&device, staged_geometry.vertices.len(), staged_geometry.indices.len(), staged_geometry.instances.len());
 
-        let render_pipeline_layout =
-            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-                label: Some("Render Pipeline Layout"),
-                bind_group_layouts: &[&camera_bind_group_layout],
-                push_constant_ranges: &[],
-            });
+        let (obj_model, obj_model_outer) = load_models(&device, &queue, &texture_bind_group_layout).await?;
 
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Render Pipeline"),
-            layout: Some(&render_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: Some("vs_main"),
-                buffers: &[
-                    Vertex::desc(),
-                    Instance::desc()
-                ],
-                compilation_options: Default::default(),
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: Some("fs_main"),
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: config.format,
-                    blend: Some(wgpu::BlendState {
-                        color: wgpu::BlendComponent::REPLACE,
-                        alpha: wgpu::BlendComponent::REPLACE,
-                    }),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-                compilation_options: Default::default(),
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Cw,
-                cull_mode: Some(wgpu::Face::Back),
-                // Setting this to anything other than Fill requires Features::POLYGON_MODE_LINE
-                // or Features::POLYGON_MODE_POINT
-                polygon_mode: wgpu::PolygonMode::Fill,
-                // Requires Features::DEPTH_CLIP_CONTROL
-                unclipped_depth: false,
-                // Requires Features::CONSERVATIVE_RASTERIZATION
-                conservative: false,
-            },
-            depth_stencil: Some(wgpu::DepthStencilState {
-                format: texture::DEPTH_FORMAT,
-                depth_write_enabled: false,
-                depth_compare: wgpu::CompareFunction::Always, // No depth test
-                stencil: wgpu::StencilState::default(), // No stencil operations
-                bias: wgpu::DepthBiasState::default(),
-            }),
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            // If the pipeline will be used with a multiview render pass, this
-            // indicates how many array layers the attachments will have.
-            multiview: None,
-            // Useful for optimizing shader compilation on Android
-            cache: None,
-        });
+        let bind_group_layouts = BindGroupLayouts {
+            camera: camera_bind_group_layout,
+            texture: texture_bind_group_layout,
+            atlas: atlas_bind_group_layout,
+        };
+        let ui_render_pipeline = PipelineKind::Ui.build(&device, &bind_group_layouts, config.format).await?;
+        let text_render_pipeline = PipelineKind::Text.build(&device, &bind_group_layouts, config.format).await?;
+        let model_render_pipeline = PipelineKind::Model.build(&device, &bind_group_layouts, config.format).await?;
+        let stencil_mask_pipeline = PipelineKind::StencilMask.build(&device, &bind_group_layouts, config.format).await?;
+        let stencil_draw_pipeline = PipelineKind::StencilDraw.build(&device, &bind_group_layouts, config.format).await?;
+        let depth_texture = texture::Texture::create_depth_texture(&device, &config, "Depth Stencil Texture");
+
+        backend_graphics_interface.update_buffer_data(&device, &queue, &staged_geometry);
+
+        let immediate_graphics_interface = BackendGraphicsInterface::initialize_buffers(&device, 0, 0, 0);
 
-        let ui_render_pipeline = PipeLineBuilder::new(&device)
-            .set_shader_module("ui_shader.wgsl", "vs_main", "fs_main")
-            .add_bind_group_layout(&camera_bind_group_layout)
-            .add_vertex_buffer_layout(Vertex::desc())
-            .add_vertex_buffer_layout(Instance::desc())
-            .set_pixel_format(wgpu::TextureFormat::Rgba8UnormSrgb)
-            .build("UI Render Pipeline").await;
-
-        let model_render_pipeline = PipeLineBuilder::new(&device)
-            .set_shader_module("model_shader.wgsl", "vs_main", "fs_main")
-            .add_bind_group_layout(&texture_bind_group_layout)
-            .add_bind_group_layout(&camera_bind_group_layout)
-            .add_vertex_buffer_layout(ModelVertex::desc())
-            .set_pixel_format(wgpu::TextureFormat::Rgba8UnormSrgb)
-            .build("Model Render Pipeline").await;
-
-        backend_graphics_interface.update_buffer_data(&queue, &vertices, &indices, &instances);
+        let mut text_renderer = TextRenderer::new(&device, &bind_group_layouts.texture);
+        text_renderer.prepare(&device, &queue, Some(&staged_ui_data.text), &[], window_size, scale_factor);
         Ok(Self {
             surface,
             device,
@@ -283,6 +361,7 @@ impl State {
             config,
             is_surface_configured: false,
             ui_render_pipeline,
+            text_render_pipeline,
             window,
 
             ui_camera,
@@ -294,14 +373,29 @@ impl State {
             model_camera_bind_group,
 
             backend_graphics_interface,
+            immediate_graphics_interface,
+            ui_atlas,
             staged_ui_data,
+            interpreted_ui,
+            immediate_ui_data: Stage::default(),
+            text_renderer,
+            scale_factor,
 
             model_render_pipeline,
+            stencil_mask_pipeline,
+            stencil_draw_pipeline,
+            depth_texture,
 
             obj_model,
             obj_model_outer,
 
             rotation_angle: 0.0,
+
+            bind_group_layouts,
+            #[cfg(not(target_arch = "wasm32"))]
+            resource_watcher: ResourceWatcher::new()
+                .inspect_err(|error| log::warn!("Resources won't be reloaded when they change: {}", error))
+                .ok(),
         })
     }
 
@@ -311,6 +405,7 @@ impl State {
             self.config.height = height;
             self.surface.configure(&self.device, &self.config);
             self.is_surface_configured = true;
+            self.depth_texture = texture::Texture::create_depth_texture(&self.device, &self.config, "Depth Stencil Texture");
 
             self.ui_camera.update_screen_size(PhysicalSize::new(width, height));
             self.queue.write_buffer(&self.ui_camera_buffer, 0, bytemuck::cast_slice(&[Camera2DUniform {
@@ -322,19 +417,140 @@ impl State {
                 view_proj: self.model_camera.build_view_projection_matrix().to_cols_array_2d(),
             }]));
 
-            let (vertices, indices, instances) = BackendGraphicsInterface::interpret_stage(self.staged_ui_data.clone(), PhysicalSize::new(width, height));
-            self.backend_graphics_interface.update_buffer_data(&self.queue, &vertices, &indices, &instances);
+            self.prepare_ui();
+        }
+    }
+
+    /// Physical pixels per logical pixel of the window.
+    pub fn scale_factor(&self) -> f32 {
+        self.scale_factor
+    }
+
+    /// Redraws the user interface for a new scale factor, e.g. when the window moved to
+    /// a display with a different pixel density.
+    pub fn set_scale_factor(&mut self, scale_factor: f64) {
+        self.scale_factor = scale_factor as f32;
+        self.prepare_ui();
+    }
+
+    /// Replaces the staged user interface.
+    pub fn set_stage(&mut self, staged_ui_data: Stage) {
+        self.staged_ui_data = staged_ui_data;
+        self.prepare_ui();
+    }
+
+    /// Applies the elements and text that changed since the user interface was staged,
+    /// interpreting only the elements that were added or changed.
+    pub fn update_stage(&mut self, update: StageUpdate) {
+        let window_size = PhysicalSize::new(self.config.width, self.config.height);
+        self.interpreted_ui.apply(&update, window_size, self.scale_factor, &self.ui_atlas.atlas);
+        let text_changed = self.staged_ui_data.apply(update);
+        self.prepare_geometry();
+        if text_changed {
+            self.prepare_text(true);
         }
     }
 
-    pub fn handle_key(&mut self, event_loop: &ActiveEventLoop, key: KeyCode, pressed: bool) {
-        match (key, pressed) {
-            (KeyCode::Escape, true) => event_loop.exit(),
-            _ => {}
+    /// Lays the whole user interface out for the current surface size and uploads it.
+    fn prepare_ui(&mut self) {
+        self.interpret_ui();
+        self.prepare_geometry();
+        self.prepare_immediate_geometry();
+        self.prepare_text(true);
+    }
+
+    /// Interprets every staged element again, e.g. for a new window size or atlas.
+    fn interpret_ui(&mut self) {
+        let window_size = PhysicalSize::new(self.config.width, self.config.height);
+        self.interpreted_ui.interpret_all(&self.staged_ui_data.elements, window_size, self.scale_factor, &self.ui_atlas.atlas);
+    }
+
+    /// Replaces the immediate-mode user interface built for this frame, which is drawn
+    /// together with the retained one. Only the immediate-mode interface is laid out again.
+    pub fn set_immediate_stage(&mut self, immediate_ui_data: Stage) {
+        self.immediate_ui_data = immediate_ui_data;
+        self.prepare_immediate_geometry();
+        self.prepare_text(false);
+    }
+
+    fn prepare_geometry(&mut self) {
+        let staged_geometry = BackendGraphicsInterface::assemble(self.interpreted_ui.elements(&self.staged_ui_data.elements));
+        self.backend_graphics_interface.update_buffer_data(&self.device, &self.queue, &staged_geometry);
+    }
+
+    fn prepare_immediate_geometry(&mut self) {
+        let window_size = PhysicalSize::new(self.config.width, self.config.height);
+        let immediate: Vec<_> = self.immediate_ui_data.elements
+            .iter()
+            .map(|element| BackendGraphicsInterface::interpret_element(element, window_size, self.scale_factor, &self.ui_atlas.atlas))
+            .collect();
+        let staged_geometry = BackendGraphicsInterface::assemble(&immediate);
+        self.immediate_graphics_interface.update_buffer_data(&self.device, &self.queue, &staged_geometry);
+    }
+
+    /// Lays out the immediate-mode text, and the retained text if it changed.
+    fn prepare_text(&mut self, retained_changed: bool) {
+        let window_size = PhysicalSize::new(self.config.width, self.config.height);
+        let retained = retained_changed.then_some(self.staged_ui_data.text.as_slice());
+        self.text_renderer.prepare(&self.device, &self.queue, retained, &self.immediate_ui_data.text, window_size, self.scale_factor);
+    }
+
+    /// Rebuilds the pipelines and reloads the models and UI atlas whose files changed since
+    /// the last call. Whatever fails to load is logged, and the last good version kept.
+    ///
+    /// Pipelines are only rebuilt with the `runtime-shaders` feature, as shaders are
+    /// otherwise embedded when compiling.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn reload_changed_resources(&mut self) {
+        let Some(watcher) = &self.resource_watcher else {
+            return;
+        };
+        let changed = watcher.changed();
+        if changed.is_empty() {
+            return;
+        }
+        let any_of = |kinds: &[ResourceKind]| changed.iter().any(|name| kinds.contains(&ResourceKind::of(name)));
+
+        let shaders_changed = |kind: &PipelineKind| cfg!(feature = "runtime-shaders") && changed.contains(kind.shader_file());
+        for kind in PipelineKind::ALL.into_iter().filter(shaders_changed) {
+            match pollster::block_on(kind.build(&self.device, &self.bind_group_layouts, self.config.format)) {
+                Ok(pipeline) => {
+                    log::info!("Reloaded {}", kind.shader_file());
+                    match kind {
+                        PipelineKind::Ui => self.ui_render_pipeline = pipeline,
+                        PipelineKind::Text => self.text_render_pipeline = pipeline,
+                        PipelineKind::Model => self.model_render_pipeline = pipeline,
+                        PipelineKind::StencilMask => self.stencil_mask_pipeline = pipeline,
+                        PipelineKind::StencilDraw => self.stencil_draw_pipeline = pipeline,
+                    }
+                }
+                Err(error) => log::error!("Keeping the last good pipeline: {:#}", error),
+            }
+        }
+
+        if any_of(&[ResourceKind::Model, ResourceKind::Texture]) {
+            match pollster::block_on(load_models(&self.device, &self.queue, &self.bind_group_layouts.texture)) {
+                Ok((obj_model, obj_model_outer)) => (self.obj_model, self.obj_model_outer) = (obj_model, obj_model_outer),
+                Err(error) => log::error!("Keeping the last good models: {:#}", error),
+            }
+        }
+
+        if any_of(&[ResourceKind::Texture]) || changed.contains("atlas.json") {
+            match pollster::block_on(atlas::load_ui_atlas(&self.device, &self.queue, &self.bind_group_layouts.atlas)) {
+                Ok(ui_atlas) => {
+                    self.ui_atlas = ui_atlas;
+                    self.interpret_ui();
+                    self.prepare_geometry();
+                }
+                Err(error) => log::error!("Keeping the last good UI atlas: {:#}", error),
+            }
         }
     }
 
     pub fn update(&mut self) {
+        #[cfg(not(target_arch = "wasm32"))]
+        self.reload_changed_resources();
+
         // Define how fast the camera should spin.
         // Smaller values are slower.
         const ROTATION_SPEED: f32 = 0.005;
@@ -364,6 +580,39 @@ impl State {
         );
     }
 
+    /// Draws the user interface back to front, switching between the retained elements,
+    /// the immediate-mode elements and the text wherever they overlap in depth.
+    fn render_ui(&self, render_pass: &mut wgpu::RenderPass) {
+        let depths = |layer: UiLayer, depths: Vec<f32>| depths.into_iter().enumerate().map(move |(draw, depth)| (depth, layer, draw));
+        let mut draws: Vec<(f32, UiLayer, usize)> = depths(UiLayer::Retained, self.backend_graphics_interface.draw_depths())
+            .chain(depths(UiLayer::Immediate, self.immediate_graphics_interface.draw_depths()))
+            .chain(depths(UiLayer::Text, self.text_renderer.draw_depths()))
+            .collect();
+        // Text goes over the elements at its own depth, and immediate-mode elements over retained ones.
+        draws.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+
+        render_pass.set_bind_group(0, &self.ui_camera_bind_group, &[]);
+        let mut remaining = draws.as_slice();
+        while let Some(&(_, layer, first)) = remaining.first() {
+            // Consecutive draws of a layer are issued together.
+            let count = remaining.iter().enumerate().take_while(|(i, (_, other, draw))| *other == layer && *draw == first + i).count();
+            let range = first..first + count;
+            match layer {
+                UiLayer::Retained | UiLayer::Immediate => {
+                    render_pass.set_pipeline(&self.ui_render_pipeline);
+                    render_pass.set_bind_group(1, &self.ui_atlas.bind_group, &[]);
+                    let geometry = if layer == UiLayer::Retained { &self.backend_graphics_interface } else { &self.immediate_graphics_interface };
+                    geometry.render(render_pass, range);
+                }
+                UiLayer::Text => {
+                    render_pass.set_pipeline(&self.text_render_pipeline);
+                    self.text_renderer.render(render_pass, range);
+                }
+            }
+            remaining = &remaining[count..];
+        }
+    }
+
     pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
         self.window.request_redraw();
 
@@ -400,28 +649,37 @@ impl State {
                         },
                         depth_slice: None,
                     })],
-                    // ATTACH THE STENCIL BUFFER HERE
-                    depth_stencil_attachment: None,
+                    depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                        view: &self.depth_texture.view,
+                        depth_ops: Some(wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(1.0),
+                            store: wgpu::StoreOp::Discard,
+                        }),
+                        stencil_ops: Some(wgpu::Operations {
+                            load: wgpu::LoadOp::Clear(0),
+                            store: wgpu::StoreOp::Discard,
+                        }),
+                    }),
                     occlusion_query_set: None,
                     timestamp_writes: None,
                 });
 
-                // Your existing UI render call is fine
-                render_pass.set_pipeline(&self.ui_render_pipeline);
-                render_pass.set_bind_group(0, &self.ui_camera_bind_group, &[]);
-                self.backend_graphics_interface.render(&mut render_pass);
+                self.render_ui(&mut render_pass);
+
+                // The UI draws leave the scissor rect of the last scroll view they clipped to.
+                render_pass.set_scissor_rect(0, 0, self.config.width, self.config.height);
 
                 // --- NEW 3-PASS DRAWING LOGIC ---
 
                 // 1. Draw green 'A' to create the stencil mask (no color is written)
-                //render_pass.set_pipeline(&self.stencil_mask_pipeline);
-                //render_pass.set_stencil_reference(1);
-                //render_pass.draw_model(&self.obj_model_outer, &self.model_camera_bind_group);
+                render_pass.set_pipeline(&self.stencil_mask_pipeline);
+                render_pass.set_stencil_reference(1);
+                render_pass.draw_model(&self.obj_model_outer, &self.model_camera_bind_group);
 
                 // 2. Draw red 'A', using the stencil mask to clip it
-                //render_pass.set_pipeline(&self.stencil_draw_pipeline);
-                //render_pass.set_stencil_reference(1);
-                //render_pass.draw_model(&self.obj_model, &self.model_camera_bind_group);
+                render_pass.set_pipeline(&self.stencil_draw_pipeline);
+                render_pass.set_stencil_reference(1);
+                render_pass.draw_model(&self.obj_model, &self.model_camera_bind_group);
                 
                 // 3. Draw the green 'A' normally so it's visible
                 render_pass.set_pipeline(&self.model_render_pipeline);
@@ -46,7 +46,9 @@ pub async fn load_model(
             ..Default::default()
         },
         |p| async move {
-            let mat_text = load_string(&p).await.unwrap();
+            let Ok(mat_text) = load_string(&p).await else {
+                return Err(tobj::LoadError::OpenFileFailed);
+            };
             tobj::load_mtl_buf(&mut BufReader::new(Cursor::new(mat_text)))
         },
     )
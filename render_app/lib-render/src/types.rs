@@ -1,10 +1,10 @@
-use std::any::Any;
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct Vertex {
     pub position: [f32; 3],
     pub color: [f32; 4],
+    pub tex_coords: [f32; 2],
 }
 
 impl Vertex {
@@ -22,23 +22,148 @@ impl Vertex {
                     offset: std::mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
                     shader_location: 1,
                     format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 7]>() as wgpu::BufferAddress,
+                    shader_location: 2,
+                    format: wgpu::VertexFormat::Float32x2,
                 }
             ]
         }
     }
 }
 
+/// A run of text to be laid out with the bundled font.
+///
+/// `position` is where the `pivot` of the text's bounds sits, in logical pixels from the
+/// window's top-left corner, with a pivot of `[0.0, 0.0]` for the top-left corner and
+/// `[1.0, 1.0]` for the bottom-right one. `size` is the font height in logical pixels.
+#[derive(Clone, Debug)]
+pub struct TextSection {
+    pub position: [f32; 2],
+    pub pivot: [f32; 2],
+    pub text: String,
+    pub size: f32,
+    pub color: [f32; 4],
+    /// Window area as `[x, y, width, height]` in logical pixels that the text is cut off
+    /// at, e.g. by a scroll view.
+    pub clip: Option<[f32; 4]>,
+    /// Where the text is drawn among the elements, see [`Instance::depth`]. Text is drawn
+    /// over the elements at the same depth.
+    pub depth: f32,
+}
+
+/// An element's instance data along with the name of the atlas sprite it is drawn with.
+///
+/// Elements without a sprite are drawn with the atlas' solid white entry.
+#[derive(Clone, Debug)]
+pub struct StagedInstance {
+    /// Identifies the element across [`StageUpdate`]s. Elements added later have larger IDs.
+    pub id: u64,
+    pub geometry: GeometryType,
+    pub instance: Instance,
+    pub sprite: Option<String>,
+    /// Width in logical pixels of a [`GeometryType::Line`] segment, whose `instance.scale` holds
+    /// the offset from its start to its end rather than a size.
+    pub line_width: Option<f32>,
+    /// Solid quads drawn right over the element, e.g. a text field's caret and selection.
+    pub overlays: Vec<Instance>,
+    /// Window area as `[x, y, width, height]` in logical pixels that the element and its
+    /// overlays are cut off at, e.g. by a scroll view.
+    pub clip: Option<[f32; 4]>,
+}
+
+/// Everything the renderer needs to draw the user interface.
+#[derive(Clone, Debug, Default)]
+pub struct Stage {
+    /// Elements in the order they were added. The renderer draws them back to front by
+    /// [`Instance::depth`], keeping that order between elements at the same depth.
+    pub elements: Vec<StagedInstance>,
+    pub text: Vec<TextSection>,
+}
+
+impl Stage {
+    /// Merges `update` into the stage. Returns whether the text changed.
+    pub fn apply(&mut self, update: StageUpdate) -> bool {
+        // Elements stay sorted by ID, which is the order they were added in.
+        self.elements.retain(|element| !update.removed.contains(&element.id));
+        for element in update.elements {
+            match self.elements.binary_search_by_key(&element.id, |staged| staged.id) {
+                Ok(index) => self.elements[index] = element,
+                Err(index) => self.elements.insert(index, element),
+            }
+        }
+
+        match update.text {
+            Some(text) => {
+                self.text = text;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// The parts of a [`Stage`] that changed since it was last staged or updated.
+#[derive(Clone, Debug, Default)]
+pub struct StageUpdate {
+    /// Elements that were added or changed.
+    pub elements: Vec<StagedInstance>,
+    /// IDs of elements that were removed.
+    pub removed: Vec<u64>,
+    /// All of the text, if any of it changed.
+    pub text: Option<Vec<TextSection>>,
+}
+
+/// The base mesh an element is drawn with. Every mesh spans `-0.5..0.5` on both axes
+/// and is scaled and moved into place per instance.
 #[derive(Hash, PartialEq, Eq, Clone, Copy, Debug)]
 pub enum GeometryType {
-    Quadrilateral
+    Quadrilateral,
+    Triangle,
+    Circle,
+    RoundedRectangle,
+    /// A quad stretched between two points, see [`StagedInstance::line_width`].
+    Line,
 }
 
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct Instance {
+    /// Centre in logical pixels from the window's top-left corner, which the renderer
+    /// turns into physical ones.
     pub position: [f32; 2],
+    /// Size in logical pixels, like `position`.
     pub scale: [f32; 2],
+    /// Atlas texture coordinates as `[u_min, v_min, u_max, v_max]`, filled in by the renderer.
+    pub tex_coords: [f32; 4],
+    /// Atlas page the texture coordinates refer to, filled in by the renderer.
+    pub tex_page: u32,
+    /// Clockwise rotation in radians around `position`.
+    pub rotation: f32,
+    /// Tint multiplied with the sprite, in linear RGBA.
+    pub color: [f32; 4],
+    /// Radius in logical pixels the corners of the instance's bounding box are rounded off with.
+    pub corner_radius: f32,
+    /// Draw order; instances with a larger depth are drawn over those with a smaller one.
+    /// Only used to sort instances, the shader never reads it.
+    pub depth: f32,
+}
+
+impl Default for Instance {
+    fn default() -> Self {
+        Self {
+            position: [0.0; 2],
+            scale: [1.0; 2],
+            tex_coords: [0.0; 4],
+            tex_page: 0,
+            rotation: 0.0,
+            color: [1.0; 4],
+            corner_radius: 0.0,
+            depth: 0.0,
+        }
+    }
 }
 
 impl Instance {
@@ -49,33 +174,40 @@ impl Instance {
             attributes: &[
                 wgpu::VertexAttribute {
                     offset: 0,
-                    shader_location: 2,
+                    shader_location: 3,
                     format: wgpu::VertexFormat::Float32x2,
                 },
                 wgpu::VertexAttribute {
                     offset: std::mem::size_of::<[f32; 2]>() as wgpu::BufferAddress,
-                    shader_location: 3,
+                    shader_location: 4,
                     format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
+                    shader_location: 5,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 8]>() as wgpu::BufferAddress,
+                    shader_location: 6,
+                    format: wgpu::VertexFormat::Uint32,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 9]>() as wgpu::BufferAddress,
+                    shader_location: 7,
+                    format: wgpu::VertexFormat::Float32,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 10]>() as wgpu::BufferAddress,
+                    shader_location: 8,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
+                wgpu::VertexAttribute {
+                    offset: std::mem::size_of::<[f32; 14]>() as wgpu::BufferAddress,
+                    shader_location: 9,
+                    format: wgpu::VertexFormat::Float32,
                 }
             ]
         }
     }
 }
-
-pub trait Element: Any {
-    fn geometry(&self) -> GeometryType;
-    fn as_instance(&self) -> Instance;
-    fn as_clickable(&self) -> Option<&dyn Clickable> {
-        None
-    }
-}
-
-pub trait Clickable {
-    fn click(&self) -> Propogate;
-}
-
-#[derive(Debug)]
-pub enum Propogate {
-    Propogate,
-    Ok
-}
\ No newline at end of file
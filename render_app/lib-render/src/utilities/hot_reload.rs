@@ -0,0 +1,78 @@
+//! Watches the resources directory so that shaders, models and textures can be reloaded
+//! while the application runs. Native builds only.
+
+use std::{collections::HashSet, path::PathBuf, sync::mpsc::{Receiver, channel}};
+
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::utilities::resource::resource_dir;
+
+/// What a changed resource is, from its extension.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ResourceKind {
+    Shader,
+    /// An `.obj` model or the `.mtl` materials it refers to.
+    Model,
+    Texture,
+    Other,
+}
+
+impl ResourceKind {
+    pub(crate) fn of(file_name: &str) -> Self {
+        let extension = file_name.rsplit_once('.').map(|(_, extension)| extension.to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("wgsl") => ResourceKind::Shader,
+            Some("obj" | "mtl") => ResourceKind::Model,
+            Some("png" | "jpg" | "jpeg" | "bmp" | "tga") => ResourceKind::Texture,
+            _ => ResourceKind::Other,
+        }
+    }
+}
+
+/// Collects the names of resources written to since they were last asked for.
+pub(crate) struct ResourceWatcher {
+    directory: PathBuf,
+    events: Receiver<notify::Result<Event>>,
+    /// Stops watching when dropped.
+    _watcher: RecommendedWatcher,
+}
+
+impl ResourceWatcher {
+    pub(crate) fn new() -> anyhow::Result<Self> {
+        let directory = resource_dir()?.canonicalize()?;
+        let (sender, events) = channel();
+        let mut watcher = notify::recommended_watcher(sender)?;
+        watcher.watch(&directory, RecursiveMode::Recursive)?;
+        Ok(Self {
+            directory,
+            events,
+            _watcher: watcher,
+        })
+    }
+
+    /// Names of the resources created or changed since the last call, as they are passed
+    /// to [`load_string`](super::resource::load_string), e.g. `"ui_shader.wgsl"`.
+    pub(crate) fn changed(&self) -> HashSet<String> {
+        let mut changed = HashSet::new();
+        for event in self.events.try_iter() {
+            let event = match event {
+                Ok(event) => event,
+                Err(error) => {
+                    log::warn!("Couldn't watch the resources: {}", error);
+                    continue;
+                }
+            };
+            // Saving a file is often several events, or a write to a temporary file
+            // renamed over the original; either way the final name is created or modified.
+            if !matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                continue;
+            }
+            for path in event.paths {
+                if let Ok(name) = path.strip_prefix(&self.directory) {
+                    changed.insert(name.to_string_lossy().replace('\\', "/"));
+                }
+            }
+        }
+        changed
+    }
+}
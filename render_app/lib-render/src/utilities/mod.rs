@@ -1,2 +1,4 @@
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) mod hot_reload;
 pub mod pipeline;
-pub mod resource;
\ No newline at end of file
+pub mod resource;
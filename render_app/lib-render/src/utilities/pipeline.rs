@@ -1,39 +1,72 @@
-use std::{env::current_dir, fs};
-
 use wgpu::{
-    BindGroupLayout, BlendState, ColorTargetState, ColorWrites, DepthBiasState, DepthStencilState, Device, Face, FragmentState, FrontFace, MultisampleState, PipelineCompilationOptions, PipelineLayoutDescriptor, PolygonMode, PrimitiveState, PrimitiveTopology, RenderPipeline, RenderPipelineDescriptor, ShaderModuleDescriptor, ShaderSource, StencilState, TextureFormat, VertexBufferLayout, VertexState
+    BindGroupLayout, BlendState, ColorTargetState, ColorWrites, DepthStencilState, Device, ErrorFilter, Face, FragmentState, FrontFace, MultisampleState, PipelineCompilationOptions, PipelineLayoutDescriptor, PolygonMode, PrimitiveState, PrimitiveTopology, RenderPipeline, RenderPipelineDescriptor, ShaderModuleDescriptor, ShaderSource, TextureFormat, VertexBufferLayout, VertexState
 };
 
-use crate::{texture::{self, Texture}, utilities::resource::load_string};
+/// The source of a shader in `resources/`, embedded when compiling unless the
+/// `runtime-shaders` feature loads it when the pipeline is built, e.g. to edit it while
+/// the application runs. Shaders other than this crate's own are always loaded.
+#[cfg(not(feature = "runtime-shaders"))]
+async fn shader_source(file_name: &str) -> anyhow::Result<String> {
+    let source = match file_name {
+        "ui_shader.wgsl" => include_str!("../../../resources/ui_shader.wgsl"),
+        "text_shader.wgsl" => include_str!("../../../resources/text_shader.wgsl"),
+        "model_shader.wgsl" => include_str!("../../../resources/model_shader.wgsl"),
+        _ => return crate::utilities::resource::load_string(file_name).await,
+    };
+    Ok(source.to_string())
+}
+
+#[cfg(feature = "runtime-shaders")]
+async fn shader_source(file_name: &str) -> anyhow::Result<String> {
+    crate::utilities::resource::load_string(file_name).await
+}
 
-pub(crate) struct PipeLineBuilder<'a> {
+/// Builds render pipelines from a shader in `resources/`. Unless set otherwise, a pipeline
+/// draws filled, clockwise triangle lists with back faces culled into one color target,
+/// blending premultiplied alpha, with one sample per pixel and no depth or stencil buffer.
+pub struct PipeLineBuilder<'a> {
     shader_filename: String,
     vertex_entry: String,
     fragment_entry: String,
-    pixel_format: wgpu::TextureFormat,
+    color_target: ColorTargetState,
+    /// Targets for the fragment shader's outputs after the first.
+    extra_color_targets: Vec<Option<ColorTargetState>>,
     vertex_buffer_layouts: Vec<VertexBufferLayout<'static>>,
     bind_group_layouts: Vec<&'a wgpu::BindGroupLayout>,
+    depth_stencil: Option<DepthStencilState>,
+    topology: PrimitiveTopology,
+    cull_mode: Option<Face>,
+    front_face: FrontFace,
+    polygon_mode: PolygonMode,
+    sample_count: u32,
     device: &'a Device,
 }
 
 impl <'a> PipeLineBuilder <'a> {
-    pub(crate) fn new(device: &'a Device) -> Self {
+    pub fn new(device: &'a Device) -> Self {
         PipeLineBuilder {
             shader_filename: "empty".to_string(),
             vertex_entry: "empty".to_string(),
             fragment_entry: "empty".to_string(),
-            pixel_format: TextureFormat::Bgra8UnormSrgb,
+            color_target: ColorTargetState {
+                format: TextureFormat::Bgra8UnormSrgb,
+                blend: Some(BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                write_mask: ColorWrites::ALL,
+            },
+            extra_color_targets: Vec::new(),
             vertex_buffer_layouts: Vec::new(),
             bind_group_layouts: Vec::new(),
-            device: device,
+            depth_stencil: None,
+            topology: PrimitiveTopology::TriangleList,
+            cull_mode: Some(Face::Back),
+            front_face: FrontFace::Cw,
+            polygon_mode: PolygonMode::Fill,
+            sample_count: 1,
+            device,
         }
     }
 
-    fn reset(&mut self) {
-        self.vertex_buffer_layouts.clear();
-    }
-
-    pub(crate) fn set_shader_module(&mut self, shader_filename: &str, vertex_entry: &str, fragment_entry: &str) -> &mut Self {
+    pub fn set_shader_module(&mut self, shader_filename: &str, vertex_entry: &str, fragment_entry: &str) -> &mut Self {
         self.shader_filename = shader_filename.to_string();
         self.vertex_entry = vertex_entry.to_string();
         self.fragment_entry = fragment_entry.to_string();
@@ -41,22 +74,82 @@ impl <'a> PipeLineBuilder <'a> {
         self
     }
 
-    pub(crate) fn add_vertex_buffer_layout(&mut self, layout: VertexBufferLayout<'static>) -> &mut Self {
+    pub fn add_vertex_buffer_layout(&mut self, layout: VertexBufferLayout<'static>) -> &mut Self {
         self.vertex_buffer_layouts.push(layout);
         self
     }
 
-    pub(crate) fn add_bind_group_layout(&mut self, layout: &'a BindGroupLayout) -> &mut Self {
+    pub fn add_bind_group_layout(&mut self, layout: &'a BindGroupLayout) -> &mut Self {
         self.bind_group_layouts.push(layout);
         self
     }
 
-    pub(crate) fn set_pixel_format(&mut self, pixel_format: TextureFormat) -> &mut Self {
-        self.pixel_format = pixel_format;
+    pub fn set_pixel_format(&mut self, pixel_format: TextureFormat) -> &mut Self {
+        self.color_target.format = pixel_format;
         self
     }
 
-    pub(crate) async fn build(&mut self, label: &str) -> RenderPipeline {
+    /// How the first color target blends with what's drawn, premultiplied alpha by
+    /// default. `None` overwrites what's already there.
+    pub fn set_blend_state(&mut self, blend: Option<BlendState>) -> &mut Self {
+        self.color_target.blend = blend;
+        self
+    }
+
+    /// Which channels of the first color target are written, e.g. none for a pass that
+    /// only writes the stencil buffer.
+    pub fn set_write_mask(&mut self, write_mask: ColorWrites) -> &mut Self {
+        self.color_target.write_mask = write_mask;
+        self
+    }
+
+    /// Adds a color target after the first, for the fragment shader's next output.
+    /// `None` leaves that output unused.
+    pub fn add_color_target(&mut self, target: Option<ColorTargetState>) -> &mut Self {
+        self.extra_color_targets.push(target);
+        self
+    }
+
+    /// Tests and writes depth and stencil values; there is no depth or stencil buffer by default.
+    pub fn set_depth_stencil(&mut self, depth_stencil: DepthStencilState) -> &mut Self {
+        self.depth_stencil = Some(depth_stencil);
+        self
+    }
+
+    pub fn set_primitive_topology(&mut self, topology: PrimitiveTopology) -> &mut Self {
+        self.topology = topology;
+        self
+    }
+
+    /// Which faces aren't drawn, back faces by default. `None` draws both.
+    pub fn set_cull_mode(&mut self, cull_mode: Option<Face>) -> &mut Self {
+        self.cull_mode = cull_mode;
+        self
+    }
+
+    /// Which winding faces the front, clockwise by default.
+    pub fn set_front_face(&mut self, front_face: FrontFace) -> &mut Self {
+        self.front_face = front_face;
+        self
+    }
+
+    /// Anything but [`PolygonMode::Fill`] needs the device to have been requested with
+    /// `Features::POLYGON_MODE_LINE` or `Features::POLYGON_MODE_POINT`.
+    pub fn set_polygon_mode(&mut self, polygon_mode: PolygonMode) -> &mut Self {
+        self.polygon_mode = polygon_mode;
+        self
+    }
+
+    /// Samples per pixel for multisample anti-aliasing, which the render pass's
+    /// attachments have to match; 1 by default.
+    pub fn set_sample_count(&mut self, sample_count: u32) -> &mut Self {
+        self.sample_count = sample_count;
+        self
+    }
+
+    /// Builds the pipeline, or returns the error if its shader can't be loaded or doesn't
+    /// compile, or the pipeline doesn't validate. The builder is then as if it was new.
+    pub async fn build(&mut self, label: &str) -> anyhow::Result<RenderPipeline> {
 
         let pipeline_layout_descriptor = PipelineLayoutDescriptor {
             label: Some("Pipeline Layout"),
@@ -64,30 +157,21 @@ impl <'a> PipeLineBuilder <'a> {
             push_constant_ranges: &[],
         };
 
-        let source_code = load_string(&self.shader_filename).await.unwrap();
+        let source_code = shader_source(&self.shader_filename).await?;
 
         let shader_module_descriptor = ShaderModuleDescriptor {
             label: Some("Shader Module"),
             source: ShaderSource::Wgsl(source_code.into()),
         };
 
+        // Catch errors rather than have the device panic on them.
+        self.device.push_error_scope(ErrorFilter::Validation);
+
         let shader_module = self.device.create_shader_module(shader_module_descriptor);
 
         let pipeline_layout = self.device.create_pipeline_layout(&pipeline_layout_descriptor);
 
-        let render_targets = [Some(ColorTargetState {
-            format: self.pixel_format,
-            blend: Some(BlendState::PREMULTIPLIED_ALPHA_BLENDING),
-            write_mask: ColorWrites::ALL,
-        })];
-
-        let _depth_stencil = DepthStencilState {
-            format: texture::DEPTH_FORMAT,
-            depth_write_enabled: false,
-            depth_compare: wgpu::CompareFunction::Always,
-            stencil: StencilState::default(),
-            bias: DepthBiasState::default(),
-        };
+        let color_targets: Vec<Option<ColorTargetState>> = std::iter::once(Some(self.color_target.clone())).chain(self.extra_color_targets.iter().cloned()).collect();
 
         let pipeline_descriptor = RenderPipelineDescriptor {
             label: Some(label),
@@ -104,21 +188,21 @@ impl <'a> PipeLineBuilder <'a> {
                 module: &shader_module,
                 entry_point: Some(&self.fragment_entry),
                 compilation_options: PipelineCompilationOptions::default(),
-                targets: &render_targets,
+                targets: &color_targets,
             }),
 
             primitive: PrimitiveState {
-                topology: PrimitiveTopology::TriangleList,
+                topology: self.topology,
                 strip_index_format: None,
-                front_face: FrontFace::Cw,
-                cull_mode: Some(Face::Back),
+                front_face: self.front_face,
+                cull_mode: self.cull_mode,
                 unclipped_depth: false,
-                polygon_mode: PolygonMode::Fill,
+                polygon_mode: self.polygon_mode,
                 conservative: false,
             },
-            depth_stencil: None,
+            depth_stencil: self.depth_stencil.clone(),
             multisample: MultisampleState {
-                count: 1,
+                count: self.sample_count,
                 mask: !0,
                 alpha_to_coverage_enabled: false,
             },
@@ -127,9 +211,15 @@ impl <'a> PipeLineBuilder <'a> {
         };
 
         let pipeline = self.device.create_render_pipeline(&pipeline_descriptor);
+        let error = self.device.pop_error_scope().await;
 
-        self.reset();
+        let result = match error {
+            Some(error) => Err(anyhow::anyhow!("couldn't build {} from {}: {}", label, self.shader_filename, error)),
+            None => Ok(pipeline),
+        };
 
-        pipeline
+        *self = PipeLineBuilder::new(self.device);
+
+        result
     }
-}
\ No newline at end of file
+}
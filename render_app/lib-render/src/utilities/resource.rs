@@ -6,9 +6,7 @@ pub async fn load_texture(
     device: &wgpu::Device,
     queue: &wgpu::Queue,
 ) -> anyhow::Result<texture::Texture> {
-    println!("file: {file_name}");
     let data = load_binary(file_name).await?;
-    println!("Flag 5");
     texture::Texture::from_bytes(device, queue, &data, file_name)
 }
 
@@ -20,12 +18,8 @@ pub async fn load_binary(file_name: &str) -> anyhow::Result<Vec<u8>> {
     };
     #[cfg(not(target_arch = "wasm32"))]
     let data = {
-        use std::env;
-
-        let mut path = env::current_dir()?;
-            path.push("resources");
-            path.push(file_name);
-            println!("{path:#?}");
+        let path = resource_dir()?.join(file_name);
+        log::debug!("Loading {}", path.display());
         std::fs::read(path)?
     };
 
@@ -41,18 +35,20 @@ pub async fn load_string(file_name: &str) -> anyhow::Result<String> {
 
     #[cfg(not(target_arch = "wasm32"))]
     let text = {
-        use std::env;
-
-        let mut path = env::current_dir()?;
-        path.push("resources");
-        path.push(file_name);
-        println!("{path:#?}");
-        std::fs::read_to_string(path).unwrap()
+        let path = resource_dir()?.join(file_name);
+        log::debug!("Loading {}", path.display());
+        std::fs::read_to_string(path)?
     };
 
     Ok(text)
 }
 
+/// The directory resources are loaded from, `resources` in the working directory.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn resource_dir() -> anyhow::Result<std::path::PathBuf> {
+    Ok(std::env::current_dir()?.join("resources"))
+}
+
 #[cfg(target_arch = "wasm32")]
 fn format_url(file_name: &str) -> reqwest::Url {
     let window = web_sys::window().unwrap();
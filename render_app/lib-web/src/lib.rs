@@ -10,8 +10,9 @@ fn web_entry() {
     let mut interface = GraphicsInterface::new();
 
     interface.show(|ui| {
-        ui.add_panel([0.0, 0.0, 1.0], [0.5, 0.5]);
+        ui.add_panel([0.0, 0.0, 1.0], [400.0, 300.0]);
     });
+    interface.load_document("ui/toolbar.ron");
 
     run_web(interface).unwrap_throw();
 }
\ No newline at end of file
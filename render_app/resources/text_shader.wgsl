@@ -0,0 +1,55 @@
+struct Camera2DUniform {
+    view_proj: mat4x4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> camera: Camera2DUniform;
+
+@group(1) @binding(0)
+var t_glyphs: texture_2d<f32>;
+@group(1) @binding(1)
+var s_glyphs: sampler;
+
+struct VertexInput {
+    @location(0) corner: vec2<f32>,
+};
+
+struct GlyphInput {
+    @location(1) position: vec2<f32>,
+    @location(2) size: vec2<f32>,
+    @location(3) tex_min: vec2<f32>,
+    @location(4) tex_max: vec2<f32>,
+    @location(5) color: vec4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) color: vec4<f32>,
+};
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+    glyph: GlyphInput
+) -> VertexOutput {
+    var out: VertexOutput;
+
+    // The glyph quad spans from its top-left position to position + size in screen pixels
+    let screen_position = glyph.position + model.corner * glyph.size;
+
+    out.clip_position = camera.view_proj * vec4<f32>(screen_position, 0.0, 1.0);
+    out.tex_coords = mix(glyph.tex_min, glyph.tex_max, model.corner);
+    out.color = glyph.color;
+    return out;
+}
+
+// Fragment shader
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // The glyph cache only stores coverage, so it scales the (premultiplied) text color
+    let coverage = textureSample(t_glyphs, s_glyphs, in.tex_coords).r;
+    let alpha = in.color.a * coverage;
+    return vec4<f32>(in.color.rgb * alpha, alpha);
+}
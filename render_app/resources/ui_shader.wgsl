@@ -5,19 +5,36 @@ struct Camera2DUniform {
 @group(0) @binding(0)
 var<uniform> camera: Camera2DUniform;
 
+@group(1) @binding(0)
+var t_atlas: texture_2d_array<f32>;
+@group(1) @binding(1)
+var s_atlas: sampler;
+
 struct VertexInput {
     @location(0) position: vec3<f32>,
     @location(1) color: vec4<f32>,
+    @location(2) tex_coords: vec2<f32>,
 };
 
 struct InstanceInput {
-    @location(2) position_offset: vec2<f32>,
-    @location(3) scale: vec2<f32>,
+    @location(3) position_offset: vec2<f32>,
+    @location(4) scale: vec2<f32>,
+    @location(5) tex_rect: vec4<f32>,
+    @location(6) tex_page: u32,
+    @location(7) rotation: f32,
+    @location(8) color: vec4<f32>,
+    @location(9) corner_radius: f32,
 };
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) color: vec4<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) @interpolate(flat) tex_page: u32,
+    // Position relative to the instance's centre in unrotated pixels, for rounding corners
+    @location(3) local_position: vec2<f32>,
+    @location(4) @interpolate(flat) half_size: vec2<f32>,
+    @location(5) @interpolate(flat) corner_radius: f32,
 };
 
 @vertex
@@ -30,13 +47,24 @@ fn vs_main(
     // First, apply the instance-specific scale to the vertex position
     let scaled_position = vec3<f32>(model.position.x * instance.scale.x, model.position.y * instance.scale.y, model.position.z);
 
+    // Rotate around the instance's centre; with y pointing down a positive angle turns clockwise
+    let c = cos(instance.rotation);
+    let s = sin(instance.rotation);
+    let rotated_position = vec3<f32>(scaled_position.x * c - scaled_position.y * s, scaled_position.x * s + scaled_position.y * c, scaled_position.z);
+
     // Then, apply the instance-specific position offset
-    let translated_position = scaled_position + vec3<f32>(instance.position_offset.x, instance.position_offset.y, 0.0);
+    let translated_position = rotated_position + vec3<f32>(instance.position_offset.x, instance.position_offset.y, 0.0);
 
     // Combine with the camera's view_proj matrix
     out.clip_position = camera.view_proj * vec4<f32>(translated_position, 1.0);
 
-    out.color = model.color;
+    out.color = model.color * instance.color;
+    // Map the quad's 0..1 texture coordinates onto this instance's atlas entry
+    out.tex_coords = mix(instance.tex_rect.xy, instance.tex_rect.zw, model.tex_coords);
+    out.tex_page = instance.tex_page;
+    out.local_position = scaled_position.xy;
+    out.half_size = abs(instance.scale) * 0.5;
+    out.corner_radius = instance.corner_radius;
     return out;
 }
 
@@ -44,5 +72,16 @@ fn vs_main(
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return vec4<f32>(in.color);
-}
\ No newline at end of file
+    var color = textureSample(t_atlas, s_atlas, in.tex_coords, in.tex_page) * in.color;
+
+    if in.corner_radius > 0.0 {
+        // Signed distance to the rounded box, antialiased over one pixel
+        let radius = min(in.corner_radius, min(in.half_size.x, in.half_size.y));
+        let q = abs(in.local_position) - in.half_size + vec2<f32>(radius);
+        let distance = length(max(q, vec2<f32>(0.0))) + min(max(q.x, q.y), 0.0) - radius;
+        color.a *= clamp(0.5 - distance, 0.0, 1.0);
+    }
+
+    // The pipeline blends with premultiplied alpha
+    return vec4<f32>(color.rgb * color.a, color.a);
+}